@@ -0,0 +1,166 @@
+use std::{fs::File, sync::OnceLock};
+
+use serde::Deserialize;
+
+use super::game_model;
+use super::message_action;
+use super::storage;
+use super::telegram_types;
+use crate::prompt_messages::achievement_unlocked;
+use crate::settings::Language;
+
+const DEFAULT_ACHIEVEMENTS: &str = include_str!("../achievements.yaml");
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Condition {
+    Held {
+        min_turn_score: u16,
+    },
+    Busted {
+        min_lost_score: u16,
+    },
+    Won {
+        min_deficit: Option<u16>,
+        max_ones: Option<u32>,
+        max_rolls: Option<u32>,
+    },
+}
+
+#[derive(Deserialize)]
+pub struct Achievement {
+    pub id: String,
+    pub name: String,
+    pub emoji: String,
+    pub description: String,
+    #[serde(with = "serde_yaml::with::singleton_map")]
+    condition: Condition,
+}
+
+impl Achievement {
+    fn is_unlocked_by(
+        &self,
+        event: &game_model::GameEvent,
+    ) -> Vec<(telegram_types::UserId, String)> {
+        match (&self.condition, event) {
+            (
+                Condition::Held { min_turn_score },
+                game_model::GameEvent::Held {
+                    user_id,
+                    name,
+                    turn_score,
+                },
+            ) if turn_score >= min_turn_score => vec![(*user_id, name.clone())],
+            (
+                Condition::Busted { min_lost_score },
+                game_model::GameEvent::Busted {
+                    user_id,
+                    name,
+                    lost_score,
+                },
+            ) if lost_score >= min_lost_score => vec![(*user_id, name.clone())],
+            (
+                Condition::Won {
+                    min_deficit,
+                    max_ones,
+                    max_rolls,
+                },
+                game_model::GameEvent::Finished(finished_game),
+            ) => finished_game
+                .players
+                .iter()
+                .filter(|player| player.user_id == finished_game.winner)
+                .filter(|player| min_deficit.is_none_or(|min| player.tally.largest_deficit >= min))
+                .filter(|player| max_ones.is_none_or(|max| player.tally.ones <= max))
+                .filter(|player| max_rolls.is_none_or(|max| player.tally.rolls <= max))
+                .map(|player| (player.user_id, player.name.clone()))
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Achievements {
+    achievements: Vec<Achievement>,
+}
+
+pub fn get_achievements() -> &'static Vec<Achievement> {
+    static ACHIEVEMENTS: OnceLock<Vec<Achievement>> = OnceLock::new();
+    ACHIEVEMENTS.get_or_init(|| {
+        let achievements = match std::env::var("ACHIEVEMENTS_FILE") {
+            Ok(file_path) => serde_yaml::from_reader::<_, Achievements>(
+                File::open(file_path).expect("Achievements YAML file can not be opened"),
+            ),
+            Err(_) => serde_yaml::from_str::<_>(DEFAULT_ACHIEVEMENTS),
+        };
+        achievements
+            .expect("Achievements YAML file is not valid")
+            .achievements
+    })
+}
+
+pub fn get_achievement(id: &str) -> Option<&'static Achievement> {
+    get_achievements()
+        .iter()
+        .find(|achievement| achievement.id == id)
+}
+
+pub fn record(
+    transaction: &mut storage::Transaction,
+    event: &game_model::GameEvent,
+    language: Language,
+) -> Vec<message_action::MessageAction> {
+    let mut actions = vec![];
+    for achievement in get_achievements() {
+        for (user_id, name) in achievement.is_unlocked_by(event) {
+            let badges = transaction.badges_mut(user_id);
+            if badges.contains(&achievement.id) {
+                continue;
+            }
+            badges.push(achievement.id.clone());
+            actions.push(message_action::MessageAction::Send(
+                message_action::MessageInfo {
+                    text: achievement_unlocked(
+                        language,
+                        &name,
+                        &achievement.emoji,
+                        &achievement.name,
+                        &achievement.description,
+                    ),
+                    reply_to_message_id: None,
+                    reply_markup: None,
+                    hint: None,
+                    is_premium: false,
+                    parse_mode: None,
+                },
+            ));
+        }
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_achievements_are_valid() {
+        let achievements = serde_yaml::from_str::<Achievements>(DEFAULT_ACHIEVEMENTS)
+            .unwrap()
+            .achievements;
+        assert_eq!(achievements.len(), 4);
+        assert!(matches!(
+            achievements[0].condition,
+            Condition::Held { min_turn_score: 40 }
+        ));
+        assert!(matches!(
+            achievements[3].condition,
+            Condition::Won {
+                min_deficit: None,
+                max_ones: Some(0),
+                max_rolls: None,
+            }
+        ));
+    }
+}
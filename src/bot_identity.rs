@@ -0,0 +1,62 @@
+use std::{sync::OnceLock, time::Duration};
+
+use super::command::Command;
+use super::telegram_client::TelegramClient;
+use super::telegram_types;
+use crate::prompt_messages::command_description;
+use crate::settings::Language;
+
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+static USERNAME: OnceLock<String> = OnceLock::new();
+
+pub fn username() -> &'static str {
+    USERNAME.get().map(String::as_str).unwrap_or_default()
+}
+
+fn language_code(language: Language) -> Option<&'static str> {
+    match language {
+        Language::English => None,
+        Language::Persian => Some("fa"),
+    }
+}
+
+// Commands can't be matched without the username, so startup waits for getMe
+// to succeed instead of running with a guessed one.
+pub async fn init(telegram: &TelegramClient) {
+    let mut delay = Duration::from_secs(1);
+    let username = loop {
+        match telegram.get_me().await {
+            Some(telegram_types::User {
+                username: Some(username),
+                ..
+            }) => break username,
+            _ => {
+                tracing::error!(
+                    "Can not get bot username, retrying in {} s",
+                    delay.as_secs()
+                );
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+            }
+        }
+    };
+    tracing::info!("Running as @{}", username);
+    let _ = USERNAME.set(username);
+
+    for language in [Language::English, Language::Persian] {
+        let commands: Vec<telegram_types::BotCommand> = Command::ALL
+            .iter()
+            .map(|command| telegram_types::BotCommand {
+                command: command.name().to_string(),
+                description: command_description(language, *command).to_string(),
+            })
+            .collect();
+        if !telegram
+            .set_my_commands(&commands, language_code(language))
+            .await
+        {
+            tracing::error!("Can not register bot commands");
+        }
+    }
+}
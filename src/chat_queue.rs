@@ -0,0 +1,107 @@
+use std::{collections::VecDeque, time::Duration};
+
+use dashmap::{mapref::entry::Entry, DashMap};
+
+use super::telegram_types;
+use super::AppState;
+
+// Called once an update is processed, whether it succeeded or not.
+pub type Done = Box<dyn FnOnce() + Send + Sync>;
+
+pub enum Job {
+    Update(Box<telegram_types::Update>, Option<Done>),
+    CheckDeadline,
+    CheckSeason,
+}
+
+pub type ChatQueues = DashMap<telegram_types::ChatId, VecDeque<Job>>;
+
+pub fn push(state: AppState, update: telegram_types::Update, done: Option<Done>) {
+    let Some(chat_id) = update.get_chat_id() else {
+        tokio::spawn(async move {
+            let _ = tokio::spawn(super::process_update(state, update)).await;
+            if let Some(done) = done {
+                done();
+            }
+        });
+        return;
+    };
+    push_job(state, chat_id, Job::Update(Box::new(update), done));
+}
+
+// Jobs are queued per chat and each busy chat gets one worker that runs them
+// strictly in arrival order, so a slow chat never holds up the others.
+// The worker removes the empty queue under the same lock it pops with, which
+// means a job pushed concurrently either lands in the queue it is still
+// draining or starts a new worker. A check that is still waiting in the queue
+// covers a repeated one, so periodic checks do not pile up behind slow sends.
+pub fn push_job(state: AppState, chat_id: telegram_types::ChatId, job: Job) {
+    match state.chat_queues.entry(chat_id) {
+        Entry::Occupied(mut queue) => {
+            let is_queued = !matches!(job, Job::Update(..))
+                && queue
+                    .get()
+                    .iter()
+                    .any(|queued| std::mem::discriminant(queued) == std::mem::discriminant(&job));
+            if !is_queued {
+                queue.get_mut().push_back(job);
+            }
+        }
+        Entry::Vacant(queue) => {
+            queue.insert(VecDeque::from([job]));
+            tokio::spawn(run(state.clone(), chat_id));
+        }
+    }
+}
+
+// Webhook requests are acknowledged before they are handled, so on shutdown
+// the queued updates get a chance to finish before the process exits.
+pub async fn drain(queues: &ChatQueues, timeout: Duration) {
+    let deadline = tokio::time::Instant::now() + timeout;
+    while !queues.is_empty() {
+        if tokio::time::Instant::now() >= deadline {
+            tracing::warn!(
+                "{} chats still had queued updates on shutdown",
+                queues.len()
+            );
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+async fn run(state: AppState, chat_id: telegram_types::ChatId) {
+    loop {
+        let job = match state.chat_queues.entry(chat_id) {
+            Entry::Occupied(mut queue) => match queue.get_mut().pop_front() {
+                Some(job) => job,
+                None => {
+                    queue.remove();
+                    return;
+                }
+            },
+            Entry::Vacant(_) => return,
+        };
+        // A panic while running one job must not leave the chat stuck.
+        let (task, done) = match job {
+            Job::Update(update, done) => (
+                tokio::spawn(super::process_update(state.clone(), *update)),
+                done,
+            ),
+            Job::CheckDeadline => (
+                tokio::spawn(super::check_deadline(state.clone(), chat_id)),
+                None,
+            ),
+            Job::CheckSeason => (
+                tokio::spawn(super::check_season(state.clone(), chat_id)),
+                None,
+            ),
+        };
+        if let Err(err) = task.await {
+            tracing::error!("Chat job failed, error: {}", err);
+        }
+        if let Some(done) = done {
+            done();
+        }
+    }
+}
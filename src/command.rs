@@ -0,0 +1,149 @@
+use super::bot_identity;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Command {
+    Join,
+    Play,
+    Hold,
+    Leave,
+    Result,
+    Reset,
+    Sub,
+    Settings,
+    Top,
+    Season,
+    Stats,
+    Profile,
+    Luck,
+}
+
+impl Command {
+    pub const ALL: [Command; 13] = [
+        Command::Join,
+        Command::Play,
+        Command::Hold,
+        Command::Leave,
+        Command::Result,
+        Command::Reset,
+        Command::Sub,
+        Command::Settings,
+        Command::Top,
+        Command::Season,
+        Command::Stats,
+        Command::Profile,
+        Command::Luck,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Join => "join",
+            Command::Play => "play",
+            Command::Hold => "hold",
+            Command::Leave => "leave",
+            Command::Result => "result",
+            Command::Reset => "reset",
+            Command::Sub => "sub",
+            Command::Settings => "settings",
+            Command::Top => "top",
+            Command::Season => "season",
+            Command::Stats => "stats",
+            Command::Profile => "profile",
+            Command::Luck => "luck",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Command> {
+        Command::ALL
+            .into_iter()
+            .find(|command| command.name().eq_ignore_ascii_case(name))
+    }
+}
+
+pub struct ParsedCommand {
+    pub command: Command,
+    pub args: Vec<String>,
+}
+
+// `command` is the text of a bot_command entity such as "/top@bot", `args` is
+// the text following it up to the next command. Commands addressed to other
+// bots and unknown commands are ignored.
+pub fn parse(command: &str, args: &str) -> Option<ParsedCommand> {
+    parse_addressed(command, args, bot_identity::username())
+}
+
+fn parse_addressed(command: &str, args: &str, bot_username: &str) -> Option<ParsedCommand> {
+    let command = command.strip_prefix('/')?;
+    let (name, username) = match command.split_once('@') {
+        Some((name, username)) => (name, Some(username)),
+        None => (command, None),
+    };
+    if username.is_some_and(|username| !username.eq_ignore_ascii_case(bot_username)) {
+        return None;
+    }
+    Some(ParsedCommand {
+        command: Command::from_name(name)?,
+        args: args.split_whitespace().map(str::to_string).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_command(command: &str, args: &str) -> Option<(Command, Vec<String>)> {
+        parse_addressed(command, args, "PigGameBot").map(|parsed| (parsed.command, parsed.args))
+    }
+
+    #[test]
+    fn commands_without_a_username_are_accepted() {
+        assert_eq!(parse_command("/join", ""), Some((Command::Join, vec![])));
+    }
+
+    #[test]
+    fn commands_addressed_to_this_bot_are_accepted() {
+        assert_eq!(
+            parse_command("/hold@PigGameBot", ""),
+            Some((Command::Hold, vec![]))
+        );
+        assert_eq!(
+            parse_command("/hold@piggamebot", ""),
+            Some((Command::Hold, vec![]))
+        );
+    }
+
+    #[test]
+    fn commands_addressed_to_other_bots_are_ignored() {
+        assert_eq!(parse_command("/join@OtherBot", ""), None);
+        assert_eq!(parse_command("/join@", ""), None);
+    }
+
+    #[test]
+    fn command_names_are_case_insensitive() {
+        assert_eq!(parse_command("/TOP", ""), Some((Command::Top, vec![])));
+        assert_eq!(
+            parse_command("/Luck@PIGGAMEBOT", ""),
+            Some((Command::Luck, vec![]))
+        );
+    }
+
+    #[test]
+    fn unknown_commands_are_ignored() {
+        assert_eq!(parse_command("/start", ""), None);
+        assert_eq!(parse_command("join", ""), None);
+    }
+
+    #[test]
+    fn arguments_are_split_on_whitespace() {
+        assert_eq!(
+            parse_command("/top", " rating  month\n2 "),
+            Some((
+                Command::Top,
+                vec!["rating".into(), "month".into(), "2".into()]
+            ))
+        );
+        assert_eq!(
+            parse_command("/season@PigGameBot", " new"),
+            Some((Command::Season, vec!["new".into()]))
+        );
+    }
+}
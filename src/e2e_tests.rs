@@ -0,0 +1,334 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+use super::bot_identity;
+use super::chat_queue;
+use super::mock_telegram::{self, ApiCall, MockTelegram};
+use super::rate_limiter::RateLimiter;
+use super::storage::InMemoryStorage;
+use super::telegram_client::TelegramClient;
+use super::{router, AppState};
+
+const CHAT_ID: i64 = -1001;
+const SUPERGROUP_ID: i64 = -1001001;
+const SECRET_TOKEN: &str = "test-secret";
+
+#[derive(Clone, Copy)]
+struct TestUser {
+    id: i64,
+    name: &'static str,
+    username: &'static str,
+}
+
+const ALICE: TestUser = TestUser {
+    id: 11,
+    name: "Alice",
+    username: "alice",
+};
+const BOB: TestUser = TestUser {
+    id: 22,
+    name: "Bob",
+    username: "bob",
+};
+const EVE: TestUser = TestUser {
+    id: 33,
+    name: "<i>Eve</i> & co",
+    username: "eve",
+};
+
+// Drives the real webhook router with scripted updates and waits for the
+// chat queues to drain before returning, so the recorded Bot API calls can be
+// asserted right away.
+struct Harness {
+    mock: MockTelegram,
+    state: AppState,
+    router: Router,
+    chat_id: i64,
+    last_update_id: i64,
+    last_message_id: i64,
+}
+
+impl Harness {
+    async fn new() -> Harness {
+        let mock = MockTelegram::start().await;
+        let telegram = TelegramClient::new(
+            mock.url.clone(),
+            "TEST".to_string(),
+            Duration::from_secs(5),
+            RateLimiter::unlimited(),
+        );
+        let state = AppState::new(Arc::new(InMemoryStorage::default()), telegram);
+        bot_identity::init(&state.telegram).await;
+        mock.take_calls();
+        let router = router(state.clone(), Some(Arc::new(SECRET_TOKEN.to_string())));
+        Harness {
+            mock,
+            state,
+            router,
+            chat_id: CHAT_ID,
+            last_update_id: 0,
+            last_message_id: 1000,
+        }
+    }
+
+    async fn post(&self, update: &Value, secret_token: &str) -> StatusCode {
+        let request = Request::post("/")
+            .header("content-type", "application/json")
+            .header("X-Telegram-Bot-Api-Secret-Token", secret_token)
+            .body(Body::from(update.to_string()))
+            .unwrap();
+        let status = self.router.clone().oneshot(request).await.unwrap().status();
+        chat_queue::drain(&self.state.chat_queues, Duration::from_secs(30)).await;
+        status
+    }
+
+    fn next_update(&mut self, payload: (&str, Value)) -> Value {
+        self.last_update_id += 1;
+        let mut update = json!({"update_id": self.last_update_id});
+        update[payload.0] = payload.1;
+        update
+    }
+
+    fn message(&mut self, user: TestUser, content: Value) -> Value {
+        self.last_message_id += 1;
+        let mut message = json!({
+            "message_id": self.last_message_id,
+            "from": {"id": user.id, "first_name": user.name, "username": user.username},
+            "chat": {"id": self.chat_id, "type": "supergroup"},
+        });
+        for (key, value) in content.as_object().unwrap() {
+            message[key] = value.clone();
+        }
+        message
+    }
+
+    async fn send(&mut self, update: Value) -> StatusCode {
+        self.post(&update, SECRET_TOKEN).await
+    }
+
+    async fn command(&mut self, user: TestUser, text: &str) -> Vec<String> {
+        mock_telegram::texts(self.command_calls(user, text).await)
+    }
+
+    async fn command_calls(&mut self, user: TestUser, text: &str) -> Vec<ApiCall> {
+        let length = text.split(' ').next().unwrap().encode_utf16().count();
+        let message = self.message(
+            user,
+            json!({
+                "text": text,
+                "entities": [{"type": "bot_command", "offset": 0, "length": length}],
+            }),
+        );
+        let update = self.next_update(("message", message));
+        assert_eq!(self.send(update).await, StatusCode::OK);
+        self.mock.take_calls()
+    }
+
+    async fn dice(&mut self, user: TestUser, value: u8) -> Vec<String> {
+        let message = self.message(user, json!({"dice": {"emoji": "🎲", "value": value}}));
+        let update = self.next_update(("message", message));
+        assert_eq!(self.send(update).await, StatusCode::OK);
+        self.mock.take_texts()
+    }
+
+    async fn callback(&mut self, user: TestUser, message_id: i64, data: &str) -> Vec<String> {
+        mock_telegram::texts(self.callback_calls(user, message_id, data).await)
+    }
+
+    async fn callback_calls(
+        &mut self,
+        user: TestUser,
+        message_id: i64,
+        data: &str,
+    ) -> Vec<ApiCall> {
+        let callback_query = json!({
+            "id": format!("query-{}", self.last_update_id + 1),
+            "from": {"id": user.id, "first_name": user.name, "username": user.username},
+            "message": {
+                "message_id": message_id,
+                "chat": {"id": self.chat_id, "type": "supergroup"},
+            },
+            "data": data,
+        });
+        let update = self.next_update(("callback_query", callback_query));
+        assert_eq!(self.send(update).await, StatusCode::OK);
+        self.mock.take_calls()
+    }
+
+    // Joins both players and starts the game, returning them in turn order.
+    async fn start_game(&mut self) -> (TestUser, TestUser) {
+        self.command(ALICE, "/join").await;
+        self.command(BOB, "/join").await;
+        let texts = self.command(ALICE, "/play").await;
+        assert!(texts[0].starts_with("The game has just started."));
+        match texts[1].as_str() {
+            "@alice" => (ALICE, BOB),
+            "@bob" => (BOB, ALICE),
+            mention => panic!("unexpected first player {}", mention),
+        }
+    }
+}
+
+fn contains(texts: &[String], needle: &str) -> bool {
+    texts.iter().any(|text| text.contains(needle))
+}
+
+#[tokio::test]
+async fn whole_game_is_played_to_the_end() {
+    let mut harness = Harness::new().await;
+    let (first, second) = harness.start_game().await;
+
+    // Rolls out of turn are ignored silently.
+    assert!(harness.dice(second, 4).await.is_empty());
+
+    let texts = harness.dice(first, 1).await;
+    assert!(contains(&texts, "You lost your turn"));
+    assert!(contains(&texts, &format!("It's {} turn", second.name)));
+
+    assert_eq!(harness.dice(second, 6).await, vec!["0 + 6 = 6"]);
+    assert_eq!(harness.dice(second, 5).await, vec!["0 + 11 = 11"]);
+    let texts = harness.command(second, "/hold").await;
+    assert!(contains(&texts, "Your total score is 11."));
+    assert!(texts.contains(&format!("@{}", first.username)));
+
+    for _ in 0..16 {
+        harness.dice(first, 6).await;
+    }
+    let texts = harness.dice(first, 6).await;
+    assert!(contains(
+        &texts,
+        &format!(
+            "<b><code>{:<13} 102</code></b> 👑",
+            format!("{} ({})", first.name, first.username)
+        )
+    ));
+    assert!(contains(
+        &texts,
+        &format!("<code>{} ({})", second.name, second.username)
+    ));
+
+    // A finished game is recorded in the leaderboard and a new one can start.
+    let texts = harness.command(first, "/top").await;
+    assert!(contains(&texts, first.name));
+    let texts = harness.command(first, "/join").await;
+    assert_eq!(texts.len(), 1);
+}
+
+#[tokio::test]
+async fn scoreboard_is_html_with_escaped_names() {
+    let mut harness = Harness::new().await;
+    harness.command(EVE, "/join").await;
+    harness.command(BOB, "/join").await;
+    harness.command(EVE, "/play").await;
+    let calls = harness.command_calls(EVE, "/result").await;
+    let scoreboard = &calls.last().unwrap().body;
+    assert_eq!(scoreboard["parse_mode"], "HTML");
+    let text = scoreboard["text"].as_str().unwrap();
+    assert!(text.contains("<code>&lt;i&gt;Eve&lt;/i&gt; &amp; co (eve)   0</code>"));
+    assert!(text.contains("<code>Bob (bob)               0</code>"));
+    assert!(!text.contains("<i>"));
+}
+
+#[tokio::test]
+async fn commands_for_other_bots_are_ignored() {
+    let mut harness = Harness::new().await;
+    assert!(harness.command(ALICE, "/join@OtherBot").await.is_empty());
+    assert_eq!(harness.command(ALICE, "/JOIN@PigGameBot").await.len(), 1);
+    assert!(contains(
+        &harness.command(BOB, "/join@piggamebot").await,
+        "joined"
+    ));
+}
+
+#[tokio::test]
+async fn luck_does_not_create_a_game() {
+    let mut harness = Harness::new().await;
+    assert_eq!(harness.command(ALICE, "/luck").await.len(), 1);
+    assert!(harness.state.storage.get_due_chats(u64::MAX).is_empty());
+}
+
+#[tokio::test]
+async fn upgrade_keeps_the_running_game() {
+    let mut harness = Harness::new().await;
+    let (first, second) = harness.start_game().await;
+    // A /join in the new supergroup is handled before the upgrade notice.
+    harness.chat_id = SUPERGROUP_ID;
+    harness.command(ALICE, "/join").await;
+    let message = harness.message(ALICE, json!({"migrate_from_chat_id": CHAT_ID}));
+    let update = harness.next_update(("message", message));
+    harness.send(update).await;
+    let texts = harness.command(first, "/result").await;
+    assert!(contains(&texts, first.name));
+    assert!(contains(&texts, second.name));
+}
+
+#[tokio::test]
+async fn leaving_a_two_player_game_resets_it() {
+    let mut harness = Harness::new().await;
+    let (first, _) = harness.start_game().await;
+    let texts = harness.command(first, "/leave").await;
+    assert_eq!(texts, vec!["Everybody left :( Game is reset."]);
+    let texts = harness.command(first, "/hold").await;
+    assert!(contains(&texts, "not started"));
+}
+
+#[tokio::test]
+async fn reset_is_confirmed_with_a_button() {
+    let mut harness = Harness::new().await;
+    let (first, _) = harness.start_game().await;
+    let texts = harness.command(first, "/reset").await;
+    assert_eq!(texts.len(), 1);
+    let texts = harness.callback(first, 1, "reset").await;
+    assert_eq!(texts, vec!["Game is reset (players should join again)."]);
+    let texts = harness.command(first, "/hold").await;
+    assert!(contains(&texts, "not started"));
+}
+
+#[tokio::test]
+async fn settings_are_changed_by_admins_only() {
+    let mut harness = Harness::new().await;
+    harness.command(ALICE, "/settings").await;
+    let calls = harness.callback_calls(ALICE, 1, "settings:target:0").await;
+    let answer = calls.last().unwrap();
+    assert_eq!(answer.method, "answerCallbackQuery");
+    assert_eq!(
+        answer.body["text"],
+        "Only chat admins can change the settings."
+    );
+    assert!(mock_telegram::texts(calls).is_empty());
+}
+
+#[tokio::test]
+async fn updates_with_a_wrong_secret_token_are_rejected() {
+    let mut harness = Harness::new().await;
+    let message = harness.message(ALICE, json!({"text": "/join"}));
+    let update = harness.next_update(("message", message));
+    assert_eq!(
+        harness.post(&update, "wrong").await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert!(harness.mock.take_calls().is_empty());
+}
+
+#[tokio::test]
+async fn retried_deliveries_are_applied_once() {
+    let mut harness = Harness::new().await;
+    let message = harness.message(
+        ALICE,
+        json!({
+            "text": "/join",
+            "entities": [{"type": "bot_command", "offset": 0, "length": 5}],
+        }),
+    );
+    let update = harness.next_update(("message", message));
+    harness.send(update.clone()).await;
+    harness.send(update).await;
+    assert_eq!(harness.mock.take_texts().len(), 1);
+}
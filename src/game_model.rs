@@ -1,25 +1,99 @@
+use crate::command::Command;
 use crate::prompt_messages::{
     already_joined, game_already_started, game_is_not_started, game_logic_error_hint, hold_hint,
-    joined, joined_hint, next_turn, next_turn_hint, not_enough_player, not_joined, not_your_turn,
-    player_left, player_left_hint, player_list_hint, reset, reset_confirm, reset_confirm_hint,
-    reset_due_lack_of_players, reset_hint, result_hint, started, started_hint, turn_lost,
-    turn_lost_hint,
+    idle_notice, idle_reset, joined, joined_hint, late_join_disabled, next_turn, next_turn_hint,
+    no_players, not_enough_player, not_joined, not_your_turn, player_left, player_left_hint,
+    player_list_hint, players_title, reset, reset_confirm, reset_confirm_hint,
+    reset_due_lack_of_players, reset_hint, result_hint, score_lost, score_lost_hint, scores_title,
+    seat_not_found, started, started_hint, substitute_not_allowed, substitute_offer,
+    substitute_offer_hint, substitute_usage, substituted, substituted_hint, take_seat, turn_lost,
+    turn_lost_hint, turn_timed_out, turn_timed_out_hint, yes,
 };
+use crate::ratings::ChatRatings;
+use crate::settings::{ChatSettings, Language, LobbyPolicy, Variant, Verbosity};
+use crate::strategy::{evaluate, Decision};
 
 use super::message_action;
 use super::telegram_types;
 use super::text_messages;
 use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+const EMPTY_LOBBY_TIMEOUT: u64 = 10 * 60;
+const IDLE_NOTICE_PERIOD: u64 = 10 * 60;
+const DEFAULT_IDLE_GAME_TIMEOUT: u64 = 6 * 60 * 60;
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+fn idle_game_timeout() -> u64 {
+    static IDLE_GAME_TIMEOUT: OnceLock<u64> = OnceLock::new();
+    *IDLE_GAME_TIMEOUT.get_or_init(|| {
+        std::env::var("IDLE_GAME_TIMEOUT")
+            .ok()
+            .and_then(|timeout| timeout.parse().ok())
+            .unwrap_or(DEFAULT_IDLE_GAME_TIMEOUT)
+    })
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+pub struct PlayerTally {
+    pub decisions: u32,
+    pub optimal_decisions: u32,
+    pub early_holds: u32,
+    pub greedy_rolls: u32,
+    pub win_chance_lost: f64,
+    pub rolls: u32,
+    pub ones: u32,
+    pub busted_points: u32,
+    pub holds: u32,
+    pub held_points: u32,
+    pub largest_turn: u16,
+    pub largest_deficit: u16,
+    pub faces: [u32; 6],
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 struct Player {
     user_id: telegram_types::UserId,
     name: String,
     username: Option<String>,
-    score: u8,
+    score: u16,
+    rating: f64,
+    tally: PlayerTally,
 }
 
 impl Player {
+    fn new(
+        user_id: telegram_types::UserId,
+        name: String,
+        username: Option<String>,
+        rating: f64,
+    ) -> Player {
+        Player {
+            user_id,
+            name,
+            username,
+            score: 0,
+            rating,
+            tally: PlayerTally::default(),
+        }
+    }
+
+    fn bank(&mut self, turn_score: u16) {
+        self.score += turn_score;
+        self.tally.holds += 1;
+        self.tally.held_points += turn_score as u32;
+        self.tally.largest_turn = self.tally.largest_turn.max(turn_score);
+    }
+
     fn get_mention_string(&self) -> String {
         match &self.username {
             Some(username) => format!("@{}", username),
@@ -38,6 +112,85 @@ impl Player {
             name
         }
     }
+
+    fn is_mentioned(&self, mention: &telegram_types::Mention) -> bool {
+        match mention {
+            telegram_types::Mention::Username(username) => self
+                .username
+                .as_ref()
+                .is_some_and(|own| own.eq_ignore_ascii_case(username)),
+            telegram_types::Mention::User(user) => self.user_id == user.id,
+        }
+    }
+}
+
+fn get_mention_name(mention: &telegram_types::Mention) -> String {
+    match mention {
+        telegram_types::Mention::Username(username) => format!("@{}", username),
+        telegram_types::Mention::User(user) => user.first_name.clone(),
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+enum SubstituteTarget {
+    Username(String),
+    User(telegram_types::UserId),
+}
+
+impl SubstituteTarget {
+    fn from(mention: &telegram_types::Mention) -> SubstituteTarget {
+        match mention {
+            telegram_types::Mention::Username(username) => {
+                SubstituteTarget::Username(username.to_string())
+            }
+            telegram_types::Mention::User(user) => SubstituteTarget::User(user.id),
+        }
+    }
+
+    fn matches(&self, user: &telegram_types::User) -> bool {
+        match self {
+            SubstituteTarget::Username(username) => user
+                .username
+                .as_ref()
+                .is_some_and(|own| own.eq_ignore_ascii_case(username)),
+            SubstituteTarget::User(user_id) => user.id == *user_id,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct FinishedPlayer {
+    pub user_id: telegram_types::UserId,
+    pub name: String,
+    pub score: u16,
+    pub tally: PlayerTally,
+}
+
+#[derive(Clone)]
+pub struct FinishedGame {
+    pub players: Vec<FinishedPlayer>,
+    pub winner: telegram_types::UserId,
+}
+
+#[derive(Clone)]
+pub enum GameEvent {
+    Held {
+        user_id: telegram_types::UserId,
+        name: String,
+        turn_score: u16,
+    },
+    Busted {
+        user_id: telegram_types::UserId,
+        name: String,
+        lost_score: u16,
+    },
+    Finished(FinishedGame),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct PendingSubstitution {
+    seat: telegram_types::UserId,
+    target: SubstituteTarget,
 }
 
 #[derive(Debug)]
@@ -48,6 +201,9 @@ enum GameLogicError {
     NotEnoughPlayers,
     AlreadyJoined,
     NotJoined,
+    LateJoinDisabled,
+    SubstituteNotAllowed,
+    SeatNotFound,
 }
 
 impl GameLogicError {
@@ -56,14 +212,18 @@ impl GameLogicError {
         reply_to_message_id: telegram_types::MessageId,
         audience_name: String,
         is_premium: bool,
+        language: Language,
     ) -> message_action::MessageAction {
         let text = match self {
-            Self::AlreadyJoined => already_joined(),
-            Self::AlreadyPlaying => game_already_started(),
-            Self::NotEnoughPlayers => not_enough_player(),
-            Self::IsNotPlaying => game_is_not_started(),
-            Self::WrongTurn => not_your_turn(),
-            Self::NotJoined => not_joined(),
+            Self::AlreadyJoined => already_joined(language),
+            Self::AlreadyPlaying => game_already_started(language),
+            Self::NotEnoughPlayers => not_enough_player(language),
+            Self::IsNotPlaying => game_is_not_started(language),
+            Self::WrongTurn => not_your_turn(language),
+            Self::NotJoined => not_joined(language),
+            Self::LateJoinDisabled => late_join_disabled(language),
+            Self::SubstituteNotAllowed => substitute_not_allowed(language),
+            Self::SeatNotFound => seat_not_found(language),
         }
         .to_string();
         message_action::MessageAction::Send(message_action::MessageInfo {
@@ -72,61 +232,87 @@ impl GameLogicError {
             reply_markup: None,
             hint: Some(game_logic_error_hint(&audience_name)),
             is_premium,
+            parse_mode: None,
         })
     }
 }
 
 enum AddDiceResult<'a> {
     Finished,
-    TurnLost(&'a Player, u8),
-    Continue(&'a Player, u8),
+    TurnLost(&'a Player, u16),
+    ScoreLost(&'a Player, u16),
+    Continue(&'a Player, u16),
 }
 
 enum LeaveResult<'a> {
     RunOutOfPlayers,
     GameContinued,
-    PlayerLeft(u8),
-    CurrentPlayerLeft(u8, &'a Player),
+    PlayerLeft(u16),
+    CurrentPlayerLeft(u16, &'a Player),
 }
 
-#[derive(Default)]
+#[derive(Serialize, Deserialize, Default, Clone)]
 pub struct NewGame {
     players: HashMap<telegram_types::UserId, Player>,
     is_premium: bool,
+    settings: ChatSettings,
+    #[serde(default)]
+    last_activity: u64,
+    #[serde(default)]
+    idle_warned: bool,
+    #[serde(skip)]
+    events: Vec<GameEvent>,
 }
 
 impl NewGame {
-    pub fn new() -> NewGame {
+    pub fn new(settings: ChatSettings) -> NewGame {
         NewGame {
             players: HashMap::new(),
             is_premium: false,
+            settings,
+            last_activity: unix_now(),
+            idle_warned: false,
+            events: Vec::new(),
         }
     }
 
     fn send_players(&self) -> message_action::MessageAction {
+        let language = self.settings.language;
         let text = if self.players.is_empty() {
-            "No players!".to_string()
+            no_players(language).to_string()
         } else {
             let players_text = self.players.values().fold("".to_string(), |res, player| {
-                format!("{}\n- {}", res, player.show(false))
+                format!("{}\n- {} [{:.0}]", res, player.show(false), player.rating)
             });
-            format!("Players:{}", players_text)
+            format!("{}{}", players_title(language), players_text)
         };
         message_action::MessageAction::Send(message_action::MessageInfo {
             text,
             reply_to_message_id: None,
             reply_markup: None,
             hint: Some(player_list_hint().to_string()),
-            is_premium: self.is_premium,
+            is_premium: self.is_premium && self.settings.ai_commentary,
+            parse_mode: None,
         })
     }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
 pub struct PlayingGame {
     players: Vec<Player>,
     turn: u8,
-    current_score: u8,
+    current_score: u16,
+    last_roll: u8,
+    turn_started_at: u64,
+    pending_substitution: Option<PendingSubstitution>,
     is_premium: bool,
+    settings: ChatSettings,
+    #[serde(default)]
+    last_activity: u64,
+    #[serde(default)]
+    idle_warned: bool,
+    #[serde(skip)]
+    events: Vec<GameEvent>,
 }
 
 impl PlayingGame {
@@ -138,7 +324,14 @@ impl PlayingGame {
             players,
             turn: 0,
             current_score: 0,
+            last_roll: 0,
+            turn_started_at: unix_now(),
+            pending_substitution: None,
             is_premium: new_game.is_premium,
+            settings: new_game.settings,
+            last_activity: new_game.last_activity,
+            idle_warned: false,
+            events: new_game.events,
         }
     }
 
@@ -160,52 +353,349 @@ impl PlayingGame {
 
     fn advance_turn(&mut self) {
         self.current_score = 0;
+        self.last_roll = 0;
+        self.turn_started_at = unix_now();
         self.turn += 1;
         self.turn %= self.players.len() as u8;
     }
 
+    fn update_deficits(&mut self) {
+        let leader_score = self
+            .players
+            .iter()
+            .map(|player| player.score)
+            .max()
+            .unwrap_or_default();
+        for player in &mut self.players {
+            player.tally.largest_deficit = player
+                .tally
+                .largest_deficit
+                .max(leader_score - player.score);
+        }
+    }
+
+    fn record_decision(&mut self, decision: Decision) {
+        // The policy is solved for classic Pig, where only a single one busts.
+        if self.settings.variant != Variant::Classic {
+            return;
+        }
+        let current_player = self.get_current_player();
+        let opponent_score = self
+            .players
+            .iter()
+            .filter(|player| player.user_id != current_player.user_id)
+            .map(|player| player.score)
+            .max()
+            .unwrap_or_default();
+        let Some(evaluation) = evaluate(
+            self.settings.target_score,
+            current_player.score,
+            opponent_score,
+            self.current_score,
+            decision,
+        ) else {
+            return;
+        };
+        let tally = &mut self.get_current_player_mut().tally;
+        tally.decisions += 1;
+        if evaluation.is_optimal {
+            tally.optimal_decisions += 1;
+        } else {
+            match decision {
+                Decision::Roll => tally.greedy_rolls += 1,
+                Decision::Hold => tally.early_holds += 1,
+            }
+        }
+        tally.win_chance_lost += evaluation.win_chance_lost;
+    }
+
+    fn bank_turn(&mut self) -> u16 {
+        let turn_score = self.current_score;
+        let player = self.get_current_player_mut();
+        player.bank(turn_score);
+        let event = GameEvent::Held {
+            user_id: player.user_id,
+            name: player.name.clone(),
+            turn_score,
+        };
+        self.events.push(event);
+        self.update_deficits();
+        turn_score
+    }
+
+    fn bust(&mut self, lost_score: u16) {
+        let player = self.get_current_player();
+        let event = GameEvent::Busted {
+            user_id: player.user_id,
+            name: player.name.clone(),
+            lost_score,
+        };
+        self.events.push(event);
+    }
+
+    fn get_finished_game(&self) -> FinishedGame {
+        FinishedGame {
+            players: self
+                .players
+                .iter()
+                .map(|player| FinishedPlayer {
+                    user_id: player.user_id,
+                    name: player.name.clone(),
+                    score: player.score,
+                    tally: player.tally,
+                })
+                .collect(),
+            winner: self.get_current_player().user_id,
+        }
+    }
+
+    // Premium results are only a prompt for the generated commentary, which is
+    // sent as plain text, so the model gets the scoreboard without markup.
     fn send_results(&self) -> message_action::MessageAction {
+        let is_premium = self.is_premium && self.settings.ai_commentary;
+        let (text, parse_mode) = if is_premium {
+            (self.plain_scoreboard(), None)
+        } else {
+            (
+                self.html_scoreboard(),
+                Some(message_action::ParseMode::Html),
+            )
+        };
+        message_action::MessageAction::Send(message_action::MessageInfo {
+            text,
+            reply_to_message_id: None,
+            reply_markup: None,
+            hint: Some(result_hint().to_string()),
+            is_premium,
+            parse_mode,
+        })
+    }
+
+    fn get_marker(&self, i: usize, player: &Player) -> Option<&'static str> {
+        if player.score >= self.settings.target_score {
+            Some(text_messages::KING_EMOJI)
+        } else if self.turn as usize == i {
+            Some(text_messages::DICE_EMOJI)
+        } else {
+            None
+        }
+    }
+
+    fn plain_scoreboard(&self) -> String {
         let players_text =
             self.players
                 .iter()
                 .enumerate()
                 .fold("".to_string(), |res, (i, player)| {
-                    if player.score >= 100 {
-                        format!(
-                            "{}\n- {} {}",
-                            res,
-                            text_messages::KING_EMOJI,
-                            player.show(true)
-                        )
-                    } else if self.turn as usize == i {
-                        format!(
-                            "{}\n- {} {}",
-                            res,
-                            text_messages::DICE_EMOJI,
-                            player.show(true)
-                        )
+                    match self.get_marker(i, player) {
+                        Some(marker) => format!("{}\n- {} {}", res, marker, player.show(true)),
+                        None => format!("{}\n- {}", res, player.show(true)),
+                    }
+                });
+        format!("{}{}", scores_title(self.settings.language), players_text)
+    }
+
+    // Rows are monospace so names and scores line up, the markers stay outside
+    // the code spans because emojis are wider than one column.
+    fn html_scoreboard(&self) -> String {
+        let name_width = self
+            .players
+            .iter()
+            .map(|player| player.show(false).chars().count())
+            .max()
+            .unwrap_or_default();
+        let leader_score = self
+            .players
+            .iter()
+            .map(|player| player.score)
+            .max()
+            .unwrap_or_default();
+        let players_text =
+            self.players
+                .iter()
+                .enumerate()
+                .fold("".to_string(), |res, (i, player)| {
+                    let row = text_messages::monospace(&format!(
+                        "{:<width$} {:>3}",
+                        player.show(false),
+                        player.score,
+                        width = name_width
+                    ));
+                    let row = if leader_score > 0 && player.score == leader_score {
+                        text_messages::bold(&row)
                     } else {
-                        format!("{}\n- {}", res, player.show(true))
+                        row
+                    };
+                    match self.get_marker(i, player) {
+                        Some(marker) => format!("{}\n{} {}", res, row, marker),
+                        None => format!("{}\n{}", res, row),
                     }
                 });
-        message_action::MessageAction::Send(message_action::MessageInfo {
-            text: format!("Scores:{}", players_text),
-            reply_to_message_id: None,
-            reply_markup: None,
-            hint: Some(result_hint().to_string()),
-            is_premium: self.is_premium,
-        })
+        format!(
+            "{}{}",
+            text_messages::escape_html(scores_title(self.settings.language)),
+            players_text
+        )
     }
 }
 
+pub enum IdleCheck {
+    Active,
+    Warned(message_action::MessageAction),
+    Expired(Vec<message_action::MessageAction>),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub enum GameState {
     New(NewGame),
     Playing(PlayingGame),
 }
 
 impl GameState {
-    pub fn new() -> GameState {
-        GameState::New(NewGame::new())
+    pub fn new(settings: ChatSettings) -> GameState {
+        GameState::New(NewGame::new(settings))
+    }
+
+    fn settings(&self) -> &ChatSettings {
+        match self {
+            GameState::New(new_game) => &new_game.settings,
+            GameState::Playing(playing_game) => &playing_game.settings,
+        }
+    }
+
+    fn events_mut(&mut self) -> &mut Vec<GameEvent> {
+        match self {
+            GameState::New(new_game) => &mut new_game.events,
+            GameState::Playing(playing_game) => &mut playing_game.events,
+        }
+    }
+
+    fn activity(&self) -> (u64, bool) {
+        match self {
+            GameState::New(new_game) => (new_game.last_activity, new_game.idle_warned),
+            GameState::Playing(playing_game) => {
+                (playing_game.last_activity, playing_game.idle_warned)
+            }
+        }
+    }
+
+    fn is_empty_lobby(&self) -> bool {
+        matches!(self, GameState::New(new_game) if new_game.players.is_empty())
+    }
+
+    // Keeps one of the games when a group is upgraded and both chats have one.
+    // The new chat's lobby may come from a /join handled before the upgrade
+    // notice, so a game already running in the old group takes its place, and
+    // so does any game when that lobby is empty.
+    pub fn merge(&mut self, other: GameState) {
+        let is_replaced = match (&*self, &other) {
+            (GameState::New(_), GameState::Playing(_)) => true,
+            (GameState::New(_), GameState::New(_)) => self.is_empty_lobby(),
+            (GameState::Playing(_), _) => false,
+        };
+        if is_replaced {
+            *self = other;
+        }
+    }
+
+    fn activity_mut(&mut self) -> (&mut u64, &mut bool) {
+        match self {
+            GameState::New(new_game) => (&mut new_game.last_activity, &mut new_game.idle_warned),
+            GameState::Playing(playing_game) => (
+                &mut playing_game.last_activity,
+                &mut playing_game.idle_warned,
+            ),
+        }
+    }
+
+    pub fn touch(&mut self, now: u64) {
+        let (last_activity, idle_warned) = self.activity_mut();
+        *last_activity = now;
+        *idle_warned = false;
+    }
+
+    // The earliest time check_turn_timeout or check_idle can change the game,
+    // so the background checks only have to look at games past it.
+    pub fn deadline(&self) -> u64 {
+        let (last_activity, idle_warned) = self.activity();
+        let idle_deadline = if self.is_empty_lobby() {
+            last_activity + EMPTY_LOBBY_TIMEOUT
+        } else if idle_warned {
+            last_activity + idle_game_timeout() + IDLE_NOTICE_PERIOD
+        } else {
+            last_activity + idle_game_timeout()
+        };
+        match self {
+            GameState::Playing(playing_game) => match playing_game.settings.turn_timeout {
+                Some(turn_timeout) => {
+                    idle_deadline.min(playing_game.turn_started_at + turn_timeout)
+                }
+                None => idle_deadline,
+            },
+            GameState::New(_) => idle_deadline,
+        }
+    }
+
+    pub fn check_idle(&mut self, now: u64) -> IdleCheck {
+        let language = self.settings().language;
+        let idle_timeout = idle_game_timeout();
+        let is_empty_lobby = self.is_empty_lobby();
+        let (last_activity, idle_warned) = self.activity_mut();
+        let idle_for = now.saturating_sub(*last_activity);
+        if is_empty_lobby {
+            return if idle_for >= EMPTY_LOBBY_TIMEOUT {
+                IdleCheck::Expired(vec![])
+            } else {
+                IdleCheck::Active
+            };
+        }
+        if idle_for >= idle_timeout + IDLE_NOTICE_PERIOD && *idle_warned {
+            IdleCheck::Expired(vec![message_action::MessageAction::Send(
+                message_action::MessageInfo {
+                    text: idle_reset(language).to_string(),
+                    reply_to_message_id: None,
+                    reply_markup: None,
+                    hint: None,
+                    is_premium: false,
+                    parse_mode: None,
+                },
+            )])
+        } else if idle_for >= idle_timeout && !*idle_warned {
+            *idle_warned = true;
+            IdleCheck::Warned(message_action::MessageAction::Send(
+                message_action::MessageInfo {
+                    text: idle_notice(language, IDLE_NOTICE_PERIOD / 60),
+                    reply_to_message_id: None,
+                    reply_markup: None,
+                    hint: None,
+                    is_premium: false,
+                    parse_mode: None,
+                },
+            ))
+        } else {
+            IdleCheck::Active
+        }
+    }
+
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(self.events_mut())
+    }
+
+    pub fn get_tallies(&self) -> Vec<(String, PlayerTally)> {
+        match self {
+            GameState::New(_) => vec![],
+            GameState::Playing(playing_game) => playing_game
+                .players
+                .iter()
+                .map(|player| (player.name.clone(), player.tally))
+                .collect(),
+        }
+    }
+
+    pub fn update_settings(&mut self, settings: ChatSettings) {
+        if let GameState::New(new_game) = self {
+            new_game.settings = settings;
+        }
     }
 
     fn join(
@@ -213,18 +703,14 @@ impl GameState {
         user_id: telegram_types::UserId,
         username: Option<String>,
         name: String,
+        rating: f64,
     ) -> Result<(), GameLogicError> {
         match self {
             GameState::New(new_game) => {
                 if let std::collections::hash_map::Entry::Vacant(e) =
                     new_game.players.entry(user_id)
                 {
-                    e.insert(Player {
-                        user_id,
-                        score: 0,
-                        name,
-                        username: username.clone(),
-                    });
+                    e.insert(Player::new(user_id, name, username.clone(), rating));
                     if crate::premium::is_premium(username.unwrap_or_default()) {
                         new_game.is_premium = true;
                     }
@@ -236,13 +722,12 @@ impl GameState {
             GameState::Playing(playing_game) => {
                 if playing_game.players.iter().any(|p| p.user_id == user_id) {
                     Err(GameLogicError::AlreadyJoined)
+                } else if playing_game.settings.lobby_policy == LobbyPolicy::Closed {
+                    Err(GameLogicError::LateJoinDisabled)
                 } else {
-                    playing_game.players.push(Player {
-                        user_id,
-                        score: 0,
-                        name,
-                        username: username.clone(),
-                    });
+                    playing_game
+                        .players
+                        .push(Player::new(user_id, name, username.clone(), rating));
                     Ok(())
                 }
             }
@@ -278,8 +763,18 @@ impl GameState {
         }
     }
 
-    fn reset(&mut self) {
-        *self = GameState::new();
+    fn reset(&mut self, settings: &ChatSettings) {
+        let events = self.take_events();
+        *self = GameState::new(*settings);
+        *self.events_mut() = events;
+    }
+
+    fn finish(&mut self, settings: &ChatSettings) {
+        if let GameState::Playing(playing_game) = self {
+            let finished_game = playing_game.get_finished_game();
+            playing_game.events.push(GameEvent::Finished(finished_game));
+        }
+        self.reset(settings);
     }
 
     fn add_dice(
@@ -289,17 +784,50 @@ impl GameState {
     ) -> Result<AddDiceResult<'_>, GameLogicError> {
         let playing_game = self.get_playing_game_mut()?;
         playing_game.check_turn(user_id)?;
+        let last_score = playing_game.current_score;
+        if last_score > 0 {
+            playing_game.record_decision(Decision::Roll);
+        }
+        let tally = &mut playing_game.get_current_player_mut().tally;
+        tally.rolls += 1;
+        if let Some(face) = value
+            .checked_sub(1)
+            .and_then(|index| tally.faces.get_mut(index as usize))
+        {
+            *face += 1;
+        }
         if value == 1 {
-            let last_score = playing_game.current_score;
+            let tally = &mut playing_game.get_current_player_mut().tally;
+            tally.ones += 1;
+            tally.busted_points += last_score as u32;
+            playing_game.bust(last_score);
             playing_game.advance_turn();
             Ok(AddDiceResult::TurnLost(
                 playing_game.get_current_player(),
                 last_score,
             ))
+        } else if value == 6
+            && playing_game.last_roll == 6
+            && playing_game.settings.variant == Variant::DoubleSix
+        {
+            let current_player = playing_game.get_current_player_mut();
+            let lost_score = current_player.score;
+            current_player.score = 0;
+            current_player.tally.busted_points += last_score as u32;
+            playing_game.bust(last_score + lost_score);
+            playing_game.update_deficits();
+            playing_game.advance_turn();
+            Ok(AddDiceResult::ScoreLost(
+                playing_game.get_current_player(),
+                lost_score,
+            ))
         } else {
-            playing_game.current_score += value;
-            if playing_game.get_current_player().score + playing_game.current_score >= 100 {
-                playing_game.get_current_player_mut().score += playing_game.current_score;
+            playing_game.last_roll = value;
+            playing_game.current_score += value as u16;
+            if playing_game.get_current_player().score + playing_game.current_score
+                >= playing_game.settings.target_score
+            {
+                playing_game.bank_turn();
                 playing_game.current_score = 0;
                 Ok(AddDiceResult::Finished)
             } else {
@@ -314,16 +842,78 @@ impl GameState {
     fn hold(
         &mut self,
         user_id: telegram_types::UserId,
-    ) -> Result<(u8, u8, &Player), GameLogicError> {
+    ) -> Result<(u16, u16, &Player), GameLogicError> {
         let playing_game = self.get_playing_game_mut()?;
         playing_game.check_turn(user_id)?;
-        playing_game.get_current_player_mut().score += playing_game.current_score;
+        playing_game.record_decision(Decision::Hold);
+        let turn_score = playing_game.bank_turn();
         let result = playing_game.get_current_player().score;
-        let turn_score = playing_game.current_score;
         playing_game.advance_turn();
         Ok((result, turn_score, playing_game.get_current_player()))
     }
 
+    fn request_substitution(
+        &mut self,
+        sender_id: telegram_types::UserId,
+        seat: Option<&telegram_types::Mention>,
+        target: SubstituteTarget,
+        is_admin: bool,
+    ) -> Result<&Player, GameLogicError> {
+        let playing_game = self.get_playing_game_mut()?;
+        let seat_index = match seat {
+            Some(mention) => playing_game
+                .players
+                .iter()
+                .position(|p| p.is_mentioned(mention))
+                .ok_or(GameLogicError::SeatNotFound)?,
+            None => playing_game
+                .players
+                .iter()
+                .position(|p| p.user_id == sender_id)
+                .ok_or(GameLogicError::NotJoined)?,
+        };
+        let player = &playing_game.players[seat_index];
+        if player.user_id != sender_id && !is_admin {
+            return Err(GameLogicError::SubstituteNotAllowed);
+        }
+        playing_game.pending_substitution = Some(PendingSubstitution {
+            seat: player.user_id,
+            target,
+        });
+        Ok(player)
+    }
+
+    fn substitute(
+        &mut self,
+        user: &telegram_types::User,
+    ) -> Result<(String, &Player, bool), GameLogicError> {
+        let playing_game = self.get_playing_game_mut()?;
+        // A player already in the game cannot take another seat, and the offer
+        // stays open for someone who can.
+        if playing_game.players.iter().any(|p| p.user_id == user.id) {
+            return Err(GameLogicError::AlreadyJoined);
+        }
+        let pending = playing_game
+            .pending_substitution
+            .take_if(|pending| pending.target.matches(user))
+            .ok_or(GameLogicError::SubstituteNotAllowed)?;
+        let seat_index = playing_game
+            .players
+            .iter()
+            .position(|p| p.user_id == pending.seat)
+            .ok_or(GameLogicError::SeatNotFound)?;
+        let player = &mut playing_game.players[seat_index];
+        let previous_name = std::mem::replace(&mut player.name, user.first_name.clone());
+        player.user_id = user.id;
+        player.username = user.username.clone();
+        player.tally = PlayerTally::default();
+        Ok((
+            previous_name,
+            &playing_game.players[seat_index],
+            playing_game.turn as usize == seat_index,
+        ))
+    }
+
     fn send_results(&self) -> message_action::MessageAction {
         match self {
             GameState::New(new_game) => new_game.send_players(),
@@ -332,40 +922,90 @@ impl GameState {
     }
 
     fn is_premium(&self) -> bool {
-        match self {
+        let is_premium = match self {
             GameState::New(new_game) => new_game.is_premium,
             GameState::Playing(playing_game) => playing_game.is_premium,
+        };
+        is_premium && self.settings().ai_commentary
+    }
+
+    pub fn check_turn_timeout(&mut self, now: u64) -> Vec<message_action::MessageAction> {
+        let is_premium = self.is_premium();
+        let GameState::Playing(playing_game) = self else {
+            return vec![];
+        };
+        let Some(turn_timeout) = playing_game.settings.turn_timeout else {
+            return vec![];
+        };
+        if now < playing_game.turn_started_at + turn_timeout {
+            return vec![];
         }
+        let language = playing_game.settings.language;
+        let skipped_player_name = playing_game.get_current_player().name.clone();
+        let last_score = playing_game.current_score;
+        playing_game.advance_turn();
+        let current_player = playing_game.get_current_player();
+        vec![
+            message_action::MessageAction::Send(message_action::MessageInfo {
+                text: turn_timed_out(language, &skipped_player_name),
+                reply_to_message_id: None,
+                reply_markup: None,
+                hint: Some(turn_timed_out_hint(&skipped_player_name, last_score)),
+                is_premium,
+                parse_mode: None,
+            }),
+            message_action::MessageAction::Send(message_action::MessageInfo {
+                text: next_turn(language, &current_player.name),
+                reply_to_message_id: None,
+                reply_markup: None,
+                hint: Some(next_turn_hint(&current_player.name)),
+                is_premium,
+                parse_mode: None,
+            }),
+            message_action::MessageAction::Send(message_action::MessageInfo {
+                text: current_player.get_mention_string(),
+                reply_to_message_id: None,
+                reply_markup: None,
+                hint: None,
+                is_premium: false,
+                parse_mode: None,
+            }),
+        ]
     }
 
     pub fn handle_dice(
         &mut self,
         message: &telegram_types::Message,
         dice_value: u8,
+        settings: &ChatSettings,
     ) -> Vec<message_action::MessageAction> {
         let is_premium = self.is_premium();
+        let language = self.settings().language;
+        let verbosity = self.settings().verbosity;
         if let Some(sender) = &message.from {
             match self.add_dice(sender.id, dice_value) {
                 Ok(AddDiceResult::Finished) => {
                     let action = self.send_results();
-                    self.reset();
+                    self.finish(settings);
                     vec![action]
                 }
                 Ok(AddDiceResult::TurnLost(current_player, last_score)) => {
                     vec![
                         message_action::MessageAction::Send(message_action::MessageInfo {
-                            text: turn_lost().to_string(),
+                            text: turn_lost(language).to_string(),
                             reply_to_message_id: Some(message.message_id),
                             reply_markup: None,
                             hint: Some(turn_lost_hint(&sender.first_name, last_score)),
                             is_premium,
+                            parse_mode: None,
                         }),
                         message_action::MessageAction::Send(message_action::MessageInfo {
-                            text: next_turn(&current_player.name),
+                            text: next_turn(language, &current_player.name),
                             reply_to_message_id: None,
                             reply_markup: None,
                             hint: Some(next_turn_hint(&current_player.name)),
                             is_premium,
+                            parse_mode: None,
                         }),
                         message_action::MessageAction::Send(message_action::MessageInfo {
                             text: current_player.get_mention_string(),
@@ -373,9 +1013,39 @@ impl GameState {
                             reply_markup: None,
                             hint: None,
                             is_premium: false,
+                            parse_mode: None,
                         }),
                     ]
                 }
+                Ok(AddDiceResult::ScoreLost(current_player, lost_score)) => {
+                    vec![
+                        message_action::MessageAction::Send(message_action::MessageInfo {
+                            text: score_lost(language).to_string(),
+                            reply_to_message_id: Some(message.message_id),
+                            reply_markup: None,
+                            hint: Some(score_lost_hint(&sender.first_name, lost_score)),
+                            is_premium,
+                            parse_mode: None,
+                        }),
+                        message_action::MessageAction::Send(message_action::MessageInfo {
+                            text: next_turn(language, &current_player.name),
+                            reply_to_message_id: None,
+                            reply_markup: None,
+                            hint: Some(next_turn_hint(&current_player.name)),
+                            is_premium,
+                            parse_mode: None,
+                        }),
+                        message_action::MessageAction::Send(message_action::MessageInfo {
+                            text: current_player.get_mention_string(),
+                            reply_to_message_id: None,
+                            reply_markup: None,
+                            hint: None,
+                            is_premium: false,
+                            parse_mode: None,
+                        }),
+                    ]
+                }
+                Ok(AddDiceResult::Continue(_, _)) if verbosity == Verbosity::Quiet => vec![],
                 Ok(AddDiceResult::Continue(current_player, current_score)) => {
                     vec![message_action::MessageAction::Send(
                         message_action::MessageInfo {
@@ -389,6 +1059,7 @@ impl GameState {
                             reply_markup: None,
                             hint: None,
                             is_premium: false,
+                            parse_mode: None,
                         },
                     )]
                 }
@@ -399,7 +1070,10 @@ impl GameState {
         }
     }
 
-    fn leave(&mut self, user_id: telegram_types::UserId) -> Result<LeaveResult, GameLogicError> {
+    fn leave(
+        &mut self,
+        user_id: telegram_types::UserId,
+    ) -> Result<LeaveResult<'_>, GameLogicError> {
         match self {
             GameState::New(new_game) => {
                 new_game.players.remove(&user_id);
@@ -442,25 +1116,34 @@ impl GameState {
     pub fn handle_command(
         &mut self,
         message: &telegram_types::Message,
-        command: &str,
+        command: Command,
+        settings: &ChatSettings,
+        ratings: Option<&ChatRatings>,
     ) -> Vec<message_action::MessageAction> {
         let is_premium = self.is_premium();
+        let language = self.settings().language;
+        let verbosity = self.settings().verbosity;
         if let Some(sender) = &message.from {
             match command {
-                "/join" | "/join@piiigdicegamebot" => {
+                Command::Join => {
+                    let rating = ratings
+                        .and_then(|ratings| ratings.get(&sender.id).copied())
+                        .unwrap_or_default();
                     match self.join(
                         sender.id,
                         sender.username.clone(),
                         sender.first_name.clone(),
+                        rating.value,
                     ) {
                         Ok(_) => {
                             vec![message_action::MessageAction::Send(
                                 message_action::MessageInfo {
-                                    text: joined().to_string(),
+                                    text: joined(language).to_string(),
                                     reply_to_message_id: Some(message.message_id),
                                     reply_markup: None,
                                     hint: joined_hint(&sender.first_name).into(),
                                     is_premium: self.is_premium(),
+                                    parse_mode: None,
                                 },
                             )]
                         }
@@ -469,19 +1152,21 @@ impl GameState {
                                 message.message_id,
                                 sender.first_name.clone(),
                                 is_premium,
+                                language,
                             )]
                         }
                     }
                 }
-                "/play" | "/play@piiigdicegamebot" => match self.play() {
+                Command::Play => match self.play() {
                     Ok(current_player) => {
                         vec![
                             message_action::MessageAction::Send(message_action::MessageInfo {
-                                text: started(&current_player.name),
+                                text: started(language, &current_player.name),
                                 reply_to_message_id: Some(message.message_id),
                                 reply_markup: None,
                                 hint: Some(started_hint(&current_player.name)),
                                 is_premium,
+                                parse_mode: None,
                             }),
                             message_action::MessageAction::Send(message_action::MessageInfo {
                                 text: current_player.get_mention_string(),
@@ -489,6 +1174,7 @@ impl GameState {
                                 reply_markup: None,
                                 hint: None,
                                 is_premium: false,
+                                parse_mode: None,
                             }),
                         ]
                     }
@@ -497,14 +1183,16 @@ impl GameState {
                             message.message_id,
                             sender.first_name.clone(),
                             is_premium,
+                            language,
                         )]
                     }
                 },
-                "/hold" | "/hold@piiigdicegamebot" => match self.hold(sender.id) {
+                Command::Hold => match self.hold(sender.id) {
                     Ok((total_score, turn_score, current_player)) => {
-                        vec![
+                        let mut actions = vec![
                             message_action::MessageAction::Send(message_action::MessageInfo {
                                 text: crate::prompt_messages::hold(
+                                    language,
                                     total_score,
                                     &current_player.name,
                                 ),
@@ -512,6 +1200,7 @@ impl GameState {
                                 reply_markup: None,
                                 hint: Some(hold_hint(&sender.first_name, turn_score, total_score)),
                                 is_premium,
+                                parse_mode: None,
                             }),
                             message_action::MessageAction::Send(message_action::MessageInfo {
                                 text: current_player.get_mention_string(),
@@ -519,88 +1208,100 @@ impl GameState {
                                 reply_markup: None,
                                 hint: None,
                                 is_premium: false,
+                                parse_mode: None,
                             }),
-                        ]
+                        ];
+                        if verbosity == Verbosity::Verbose {
+                            actions.push(self.send_results());
+                        }
+                        actions
                     }
                     Err(err) => {
                         vec![err.get_reply_message(
                             message.message_id,
                             sender.first_name.clone(),
                             is_premium,
+                            language,
                         )]
                     }
                 },
-                "/result" | "/result@piiigdicegamebot" => {
+                Command::Result => {
                     vec![self.send_results()]
                 }
-                "/reset" | "/reset@piiigdicegamebot" => {
+                Command::Reset => {
                     vec![message_action::MessageAction::Send(
                         message_action::MessageInfo {
-                            text: reset_confirm().to_string(),
+                            text: reset_confirm(language).to_string(),
                             reply_to_message_id: Some(message.message_id),
                             reply_markup: Some(telegram_types::ReplyMarkup {
                                 inline_keyboard: Some(vec![vec![
                                     telegram_types::InlineKeyboardButton {
-                                        text: "Yes".to_string(),
+                                        text: yes(language).to_string(),
                                         callback_data: Some("reset".to_string()),
                                     },
                                 ]]),
                             }),
                             hint: Some(reset_confirm_hint(&sender.first_name)),
                             is_premium,
+                            parse_mode: None,
                         },
                     )]
                 }
-                "/leave" | "/leave@piiigdicegamebot" => match self.leave(sender.id) {
+                Command::Leave => match self.leave(sender.id) {
                     Ok(LeaveResult::RunOutOfPlayers) => {
-                        self.reset();
+                        self.reset(settings);
                         vec![message_action::MessageAction::Send(
                             message_action::MessageInfo {
-                                text: reset_due_lack_of_players().to_string(),
+                                text: reset_due_lack_of_players(language).to_string(),
                                 reply_to_message_id: Some(message.message_id),
                                 reply_markup: None,
                                 hint: Some(reset_hint().to_string()),
                                 is_premium,
+                                parse_mode: None,
                             },
                         )]
                     }
                     Ok(LeaveResult::GameContinued) => {
                         vec![message_action::MessageAction::Send(
                             message_action::MessageInfo {
-                                text: player_left().to_string(),
+                                text: player_left(language).to_string(),
                                 reply_to_message_id: Some(message.message_id),
                                 reply_markup: None,
                                 hint: Some(player_left_hint(&sender.first_name, 0).to_string()),
                                 is_premium,
+                                parse_mode: None,
                             },
                         )]
                     }
                     Ok(LeaveResult::PlayerLeft(score)) => {
                         vec![message_action::MessageAction::Send(
                             message_action::MessageInfo {
-                                text: player_left().to_string(),
+                                text: player_left(language).to_string(),
                                 reply_to_message_id: Some(message.message_id),
                                 reply_markup: None,
                                 hint: Some(player_left_hint(&sender.first_name, score).to_string()),
                                 is_premium,
+                                parse_mode: None,
                             },
                         )]
                     }
                     Ok(LeaveResult::CurrentPlayerLeft(score, current_player)) => {
                         vec![
                             message_action::MessageAction::Send(message_action::MessageInfo {
-                                text: player_left().to_string(),
+                                text: player_left(language).to_string(),
                                 reply_to_message_id: Some(message.message_id),
                                 reply_markup: None,
                                 hint: Some(player_left_hint(&sender.first_name, score).to_string()),
                                 is_premium,
+                                parse_mode: None,
                             }),
                             message_action::MessageAction::Send(message_action::MessageInfo {
-                                text: next_turn(&current_player.name),
+                                text: next_turn(language, &current_player.name),
                                 reply_to_message_id: None,
                                 reply_markup: None,
                                 hint: Some(next_turn_hint(&current_player.name)),
                                 is_premium,
+                                parse_mode: None,
                             }),
                             message_action::MessageAction::Send(message_action::MessageInfo {
                                 text: current_player.get_mention_string(),
@@ -608,6 +1309,7 @@ impl GameState {
                                 reply_markup: None,
                                 hint: None,
                                 is_premium: false,
+                                parse_mode: None,
                             }),
                         ]
                     }
@@ -616,6 +1318,7 @@ impl GameState {
                             message.message_id,
                             sender.first_name.clone(),
                             is_premium,
+                            language,
                         )]
                     }
                 },
@@ -626,30 +1329,176 @@ impl GameState {
         }
     }
 
+    pub fn handle_substitute_command(
+        &mut self,
+        message: &telegram_types::Message,
+        is_admin: bool,
+    ) -> Vec<message_action::MessageAction> {
+        let is_premium = self.is_premium();
+        let language = self.settings().language;
+        let Some(sender) = &message.from else {
+            return vec![];
+        };
+        let mentions = message.get_mentions();
+        let replied_user = message
+            .reply_to_message
+            .as_ref()
+            .and_then(|replied| replied.from.as_ref());
+        let (seat, target, substitute_name) = match (replied_user, mentions.as_slice()) {
+            (Some(user), [seat, ..]) => (
+                Some(seat),
+                SubstituteTarget::User(user.id),
+                user.first_name.clone(),
+            ),
+            (Some(user), []) => (
+                None,
+                SubstituteTarget::User(user.id),
+                user.first_name.clone(),
+            ),
+            (None, [seat, target, ..]) => (
+                Some(seat),
+                SubstituteTarget::from(target),
+                get_mention_name(target),
+            ),
+            (None, [target]) => (
+                None,
+                SubstituteTarget::from(target),
+                get_mention_name(target),
+            ),
+            (None, []) => {
+                return vec![message_action::MessageAction::Send(
+                    message_action::MessageInfo {
+                        text: substitute_usage(language).to_string(),
+                        reply_to_message_id: Some(message.message_id),
+                        reply_markup: None,
+                        hint: None,
+                        is_premium: false,
+                        parse_mode: None,
+                    },
+                )];
+            }
+        };
+        match self.request_substitution(sender.id, seat, target, is_admin) {
+            Ok(player) => {
+                vec![message_action::MessageAction::Send(
+                    message_action::MessageInfo {
+                        text: substitute_offer(
+                            language,
+                            &substitute_name,
+                            &player.name,
+                            player.score,
+                        ),
+                        reply_to_message_id: Some(message.message_id),
+                        reply_markup: Some(telegram_types::ReplyMarkup {
+                            inline_keyboard: Some(vec![vec![
+                                telegram_types::InlineKeyboardButton {
+                                    text: take_seat(language).to_string(),
+                                    callback_data: Some("substitute".to_string()),
+                                },
+                            ]]),
+                        }),
+                        hint: Some(substitute_offer_hint(
+                            &substitute_name,
+                            &player.name,
+                            player.score,
+                        )),
+                        is_premium,
+                        parse_mode: None,
+                    },
+                )]
+            }
+            Err(err) => {
+                vec![err.get_reply_message(
+                    message.message_id,
+                    sender.first_name.clone(),
+                    is_premium,
+                    language,
+                )]
+            }
+        }
+    }
+
+    fn handle_substitute_callback(
+        &mut self,
+        message: &telegram_types::Message,
+        from: &telegram_types::User,
+    ) -> Vec<message_action::MessageAction> {
+        let is_premium = self.is_premium();
+        let language = self.settings().language;
+        match self.substitute(from) {
+            Ok((previous_name, player, is_current_player)) => {
+                let mut actions = vec![message_action::MessageAction::Edit(
+                    message_action::EditMessageInfo {
+                        message_id: message.message_id,
+                        message_info: message_action::MessageInfo {
+                            text: substituted(language, &player.name, &previous_name, player.score),
+                            reply_to_message_id: None,
+                            reply_markup: Some(telegram_types::ReplyMarkup {
+                                inline_keyboard: Some(vec![vec![]]),
+                            }),
+                            hint: Some(substituted_hint(
+                                &player.name,
+                                &previous_name,
+                                player.score,
+                            )),
+                            is_premium,
+                            parse_mode: None,
+                        },
+                    },
+                )];
+                if is_current_player {
+                    actions.push(message_action::MessageAction::Send(
+                        message_action::MessageInfo {
+                            text: player.get_mention_string(),
+                            reply_to_message_id: None,
+                            reply_markup: None,
+                            hint: None,
+                            is_premium: false,
+                            parse_mode: None,
+                        },
+                    ));
+                }
+                actions
+            }
+            Err(error) => vec![error.get_reply_message(
+                message.message_id,
+                from.first_name.clone(),
+                is_premium,
+                language,
+            )],
+        }
+    }
+
     pub fn handle_callback_query(
         &mut self,
         message: &telegram_types::Message,
+        from: &telegram_types::User,
         data: Option<String>,
+        settings: &ChatSettings,
     ) -> Vec<message_action::MessageAction> {
         if let Some(command) = data {
             if command.as_str() == "reset" {
                 let is_premium = self.is_premium();
-                self.reset();
+                let language = self.settings().language;
+                self.reset(settings);
 
                 vec![message_action::MessageAction::Edit(
                     message_action::EditMessageInfo {
                         message_id: message.message_id,
                         message_info: message_action::MessageInfo {
-                            text: reset().to_string(),
+                            text: reset(language).to_string(),
                             reply_to_message_id: None,
                             reply_markup: Some(telegram_types::ReplyMarkup {
                                 inline_keyboard: Some(vec![vec![]]),
                             }),
                             hint: Some(reset_hint().to_string()),
                             is_premium,
+                            parse_mode: None,
                         },
                     },
                 )]
+            } else if command.as_str() == "substitute" {
+                self.handle_substitute_callback(message, from)
             } else {
                 vec![]
             }
@@ -658,3 +1507,123 @@ impl GameState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn message(user_id: i64, name: &str) -> telegram_types::Message {
+        serde_json::from_value(serde_json::json!({
+            "message_id": 1,
+            "from": {"id": user_id, "first_name": name},
+            "chat": {"id": -1, "type": "group"},
+        }))
+        .unwrap()
+    }
+
+    fn join(game: &mut GameState, user_id: i64, name: &str, settings: &ChatSettings) {
+        game.handle_command(&message(user_id, name), Command::Join, settings, None);
+    }
+
+    fn lobby(settings: &ChatSettings) -> GameState {
+        let mut game = GameState::new(*settings);
+        join(&mut game, 1, "Alice", settings);
+        game.touch(NOW);
+        game
+    }
+
+    #[test]
+    fn idle_game_is_warned_and_then_expired() {
+        let mut game = lobby(&ChatSettings::default());
+        let timeout = idle_game_timeout();
+        assert_eq!(game.deadline(), NOW + timeout);
+        assert!(matches!(
+            game.check_idle(NOW + timeout - 1),
+            IdleCheck::Active
+        ));
+        assert!(matches!(
+            game.check_idle(NOW + timeout),
+            IdleCheck::Warned(_)
+        ));
+        assert_eq!(game.deadline(), NOW + timeout + IDLE_NOTICE_PERIOD);
+        // The notice is sent once.
+        assert!(matches!(
+            game.check_idle(NOW + timeout + 1),
+            IdleCheck::Active
+        ));
+        assert!(matches!(
+            game.check_idle(NOW + timeout + IDLE_NOTICE_PERIOD),
+            IdleCheck::Expired(actions) if actions.len() == 1
+        ));
+    }
+
+    #[test]
+    fn activity_after_the_notice_keeps_the_game() {
+        let mut game = lobby(&ChatSettings::default());
+        let timeout = idle_game_timeout();
+        assert!(matches!(
+            game.check_idle(NOW + timeout),
+            IdleCheck::Warned(_)
+        ));
+        game.touch(NOW + timeout + 1);
+        assert_eq!(game.deadline(), NOW + 2 * timeout + 1);
+        assert!(matches!(
+            game.check_idle(NOW + timeout + IDLE_NOTICE_PERIOD),
+            IdleCheck::Active
+        ));
+    }
+
+    #[test]
+    fn empty_lobby_expires_without_a_notice() {
+        let mut game = GameState::new(ChatSettings::default());
+        game.touch(NOW);
+        assert_eq!(game.deadline(), NOW + EMPTY_LOBBY_TIMEOUT);
+        assert!(matches!(
+            game.check_idle(NOW + EMPTY_LOBBY_TIMEOUT),
+            IdleCheck::Expired(actions) if actions.is_empty()
+        ));
+    }
+
+    #[test]
+    fn turn_timeout_comes_before_the_idle_deadline() {
+        let settings = ChatSettings {
+            turn_timeout: Some(60),
+            ..ChatSettings::default()
+        };
+        let mut game = lobby(&settings);
+        join(&mut game, 2, "Bob", &settings);
+        let started_at = unix_now();
+        game.handle_command(&message(1, "Alice"), Command::Play, &settings, None);
+        game.touch(started_at);
+        assert!(matches!(game, GameState::Playing(_)));
+        assert!((started_at + 60..=unix_now() + 60).contains(&game.deadline()));
+    }
+
+    #[test]
+    fn seated_player_cannot_take_the_offered_seat() {
+        let settings = ChatSettings::default();
+        let mut game = lobby(&settings);
+        join(&mut game, 2, "Bob", &settings);
+        game.handle_command(&message(1, "Alice"), Command::Play, &settings, None);
+        let bob = message(2, "Bob").from.unwrap();
+        game.request_substitution(
+            message(1, "Alice").from.unwrap().id,
+            None,
+            SubstituteTarget::User(bob.id),
+            false,
+        )
+        .unwrap();
+        let actions = game.handle_substitute_callback(&message(1, "Alice"), &bob);
+        assert!(matches!(
+            actions.as_slice(),
+            [message_action::MessageAction::Send(info)]
+                if info.text == already_joined(settings.language)
+        ));
+        let GameState::Playing(playing_game) = &game else {
+            panic!("the game is no longer running");
+        };
+        assert!(playing_game.pending_substitution.is_some());
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::snapshot;
+use super::storage::{Changes, Data, MAX_APPLIED_UPDATES};
+use super::telegram_types;
+
+const DEFAULT_JOURNAL_FILE: &str = "games.journal";
+
+// Every entry carries the changes of one storage transaction. Entries are
+// numbered, so a replay skips the ones the snapshot already contains.
+#[derive(Serialize, Deserialize)]
+struct JournalEntry<T> {
+    sequence: u64,
+    update_id: Option<telegram_types::UpdateId>,
+    changes: T,
+}
+
+// Chats are processed in parallel, so an update can be stored after later
+// ones and only an explicit set of ids tells which updates are done. The oldest
+// ids are forgotten once there are too many of them.
+#[derive(Default)]
+struct AppliedUpdates {
+    ids: HashSet<telegram_types::UpdateId>,
+    order: VecDeque<telegram_types::UpdateId>,
+}
+
+impl AppliedUpdates {
+    fn insert(&mut self, update_id: telegram_types::UpdateId) {
+        if !self.ids.insert(update_id) {
+            return;
+        }
+        self.order.push_back(update_id);
+        if self.order.len() > MAX_APPLIED_UPDATES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+    }
+}
+
+pub struct Journal {
+    path: PathBuf,
+    snapshot_path: PathBuf,
+    file: File,
+    sequence: u64,
+    applied: AppliedUpdates,
+}
+
+pub fn get_journal_path() -> PathBuf {
+    std::env::var("JOURNAL_FILE")
+        .unwrap_or(DEFAULT_JOURNAL_FILE.to_string())
+        .into()
+}
+
+impl Journal {
+    // Loads the snapshot and replays the journal entries written after it.
+    pub fn open(path: PathBuf, snapshot_path: PathBuf) -> std::io::Result<(Data, Journal)> {
+        let mut snapshot = snapshot::load(&snapshot_path);
+        let mut sequence = snapshot.sequence;
+        let mut applied = AppliedUpdates::default();
+        for update_id in snapshot.applied_updates {
+            applied.insert(update_id);
+        }
+        if let Ok(file) = File::open(&path) {
+            // A crash in the middle of a write leaves a torn last line behind,
+            // which is where the replay stops.
+            for line in BufReader::new(file).lines() {
+                let Ok(entry) = serde_json::from_str::<JournalEntry<Changes>>(&line?) else {
+                    break;
+                };
+                if entry.sequence <= snapshot.sequence {
+                    continue;
+                }
+                snapshot.data.apply(entry.changes);
+                sequence = entry.sequence;
+                if let Some(update_id) = entry.update_id {
+                    applied.insert(update_id);
+                }
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let journal = Journal {
+            path,
+            snapshot_path,
+            file,
+            sequence,
+            applied,
+        };
+        Ok((snapshot.data, journal))
+    }
+
+    pub fn is_applied(&self, update_id: telegram_types::UpdateId) -> bool {
+        self.applied.ids.contains(&update_id)
+    }
+
+    pub fn append(
+        &mut self,
+        update_id: Option<telegram_types::UpdateId>,
+        changes: &Changes,
+    ) -> std::io::Result<()> {
+        if update_id.is_none() && changes.is_empty() {
+            return Ok(());
+        }
+        let mut line = serde_json::to_vec(&JournalEntry {
+            sequence: self.sequence + 1,
+            update_id,
+            changes,
+        })?;
+        line.push(b'\n');
+        // A refused transaction must not come back on replay, and a torn line
+        // would hide every entry written after it, so a failed write is cut off.
+        let length = self.file.metadata()?.len();
+        if let Err(err) = self
+            .file
+            .write_all(&line)
+            .and_then(|()| self.file.sync_data())
+        {
+            let _ = self.file.set_len(length);
+            return Err(err);
+        }
+        self.sequence += 1;
+        if let Some(update_id) = update_id {
+            self.applied.insert(update_id);
+        }
+        Ok(())
+    }
+
+    // Runs with the storage locked, so no entry can be appended between taking
+    // the snapshot and truncating the journal.
+    pub fn compact(&mut self, data: &Data) -> std::io::Result<()> {
+        snapshot::save(
+            &self.snapshot_path,
+            data,
+            &self.applied.order,
+            self.sequence,
+        )?;
+        self.file = File::create(&self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{InMemoryStorage, Storage};
+
+    fn open(directory: &std::path::Path) -> InMemoryStorage {
+        let (data, journal) = Journal::open(
+            directory.join("games.journal"),
+            directory.join("games.json"),
+        )
+        .unwrap();
+        InMemoryStorage::new(data, Some(journal))
+    }
+
+    fn update_id(id: i64) -> telegram_types::UpdateId {
+        serde_json::from_value(serde_json::json!(id)).unwrap()
+    }
+
+    fn play(storage: &InMemoryStorage, id: i64, player: telegram_types::UserId) {
+        storage.transaction(Some(update_id(id)), &mut |transaction| {
+            transaction.stats_mut(player).games_played += 1;
+        });
+    }
+
+    fn games_played(storage: &InMemoryStorage, player: telegram_types::UserId) -> u32 {
+        let mut games_played = 0;
+        storage.transaction(None, &mut |transaction| {
+            games_played = transaction.stats(player).unwrap_or_default().games_played;
+        });
+        games_played
+    }
+
+    #[test]
+    fn compaction_keeps_updates_that_are_still_queued() {
+        let directory =
+            std::env::temp_dir().join(format!("piggame-journal-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let player: telegram_types::UserId = serde_json::from_str("7").unwrap();
+        let storage = open(&directory);
+        // Update 2 is still waiting behind a slow chat when 3 is done and the
+        // snapshot is taken.
+        play(&storage, 3, player);
+        storage.compact().unwrap();
+        assert!(!storage.is_applied(update_id(2)));
+        play(&storage, 2, player);
+        drop(storage);
+
+        let storage = open(&directory);
+        assert!(storage.is_applied(update_id(2)));
+        assert!(storage.is_applied(update_id(3)));
+        assert!(!storage.is_applied(update_id(4)));
+        assert_eq!(games_played(&storage, player), 2);
+        storage.compact().unwrap();
+        drop(storage);
+
+        let storage = open(&directory);
+        assert!(storage.is_applied(update_id(2)));
+        assert_eq!(games_played(&storage, player), 2);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,420 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::game_model;
+use super::message_action;
+use super::storage;
+use super::telegram_types;
+use crate::prompt_messages::{
+    board_title, next_page, no_ranked_players, period_title, previous_page, rating_entry,
+    season_not_found, win_rate_entry, wins_entry,
+};
+use crate::ratings::ChatRatings;
+use crate::seasons::ChatSeasons;
+use crate::settings::Language;
+
+const CALLBACK_PREFIX: &str = "top";
+const PAGE_SIZE: usize = 10;
+const MIN_GAMES_FOR_WIN_RATE: u32 = 5;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RecordedPlayer {
+    pub user_id: telegram_types::UserId,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GameRecord {
+    pub finished_at: u64,
+    pub players: Vec<RecordedPlayer>,
+    pub winner: telegram_types::UserId,
+}
+
+impl GameRecord {
+    pub fn from(finished_game: &game_model::FinishedGame, finished_at: u64) -> GameRecord {
+        GameRecord {
+            finished_at,
+            players: finished_game
+                .players
+                .iter()
+                .map(|player| RecordedPlayer {
+                    user_id: player.user_id,
+                    name: player.name.clone(),
+                })
+                .collect(),
+            winner: finished_game.winner,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum Board {
+    Wins,
+    WinRate,
+    Rating,
+}
+
+impl Board {
+    const ALL: [Board; 3] = [Board::Wins, Board::WinRate, Board::Rating];
+
+    fn key(&self) -> &'static str {
+        match self {
+            Board::Wins => "wins",
+            Board::WinRate => "rate",
+            Board::Rating => "rating",
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum Period {
+    Week,
+    Month,
+    AllTime,
+    Season(u32),
+}
+
+impl Period {
+    const ALL: [Period; 3] = [Period::Week, Period::Month, Period::AllTime];
+
+    fn key(&self) -> String {
+        match self {
+            Period::Week => "week".to_string(),
+            Period::Month => "month".to_string(),
+            Period::AllTime => "all".to_string(),
+            Period::Season(number) => format!("season{}", number),
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Period> {
+        match key.strip_prefix("season") {
+            Some(number) => number.parse().ok().map(Period::Season),
+            None => Period::ALL.into_iter().find(|option| option.key() == key),
+        }
+    }
+
+    pub fn start(&self) -> u64 {
+        let today = Utc::now().date_naive();
+        let start = match self {
+            Period::Week => {
+                today - chrono::Days::new(today.weekday().num_days_from_monday() as u64)
+            }
+            Period::Month => {
+                NaiveDate::from_ymd_opt(today.year(), today.month(), 1).unwrap_or(today)
+            }
+            Period::AllTime | Period::Season(_) => return 0,
+        };
+        start
+            .and_hms_opt(0, 0, 0)
+            .map(|start| start.and_utc().timestamp() as u64)
+            .unwrap_or_default()
+    }
+}
+
+pub struct Entry {
+    pub name: String,
+    pub games: u32,
+    pub wins: u32,
+    pub rating: f64,
+}
+
+impl Entry {
+    fn win_rate(&self) -> f64 {
+        self.wins as f64 / self.games as f64
+    }
+}
+
+pub fn get_entries(
+    records: &[GameRecord],
+    ratings: Option<&ChatRatings>,
+    board: Board,
+    (start, end): (u64, u64),
+) -> Vec<Entry> {
+    let mut entries: HashMap<telegram_types::UserId, Entry> = HashMap::new();
+    for record in records
+        .iter()
+        .filter(|record| record.finished_at >= start && record.finished_at < end)
+    {
+        for player in &record.players {
+            let entry = entries.entry(player.user_id).or_insert(Entry {
+                name: String::new(),
+                games: 0,
+                wins: 0,
+                rating: ratings
+                    .and_then(|ratings| ratings.get(&player.user_id).copied())
+                    .unwrap_or_default()
+                    .value,
+            });
+            entry.name = player.name.clone();
+            entry.games += 1;
+            if player.user_id == record.winner {
+                entry.wins += 1;
+            }
+        }
+    }
+    let mut entries: Vec<Entry> = entries.into_values().collect();
+    match board {
+        Board::Wins => {
+            entries.retain(|entry| entry.wins > 0);
+            entries.sort_by(|a, b| b.wins.cmp(&a.wins).then(a.games.cmp(&b.games)));
+        }
+        Board::WinRate => {
+            entries.retain(|entry| entry.games >= MIN_GAMES_FOR_WIN_RATE);
+            entries.sort_by(|a, b| {
+                b.win_rate()
+                    .total_cmp(&a.win_rate())
+                    .then(b.games.cmp(&a.games))
+            });
+        }
+        Board::Rating => {
+            entries.sort_by(|a, b| b.rating.total_cmp(&a.rating));
+        }
+    }
+    entries
+}
+
+fn button(
+    text: String,
+    board: Board,
+    period: Period,
+    page: usize,
+) -> telegram_types::InlineKeyboardButton {
+    telegram_types::InlineKeyboardButton {
+        text,
+        callback_data: Some(format!(
+            "{}:{}:{}:{}",
+            CALLBACK_PREFIX,
+            board.key(),
+            period.key(),
+            page
+        )),
+    }
+}
+
+fn selected(text: &str, is_selected: bool) -> String {
+    if is_selected {
+        format!("• {} •", text)
+    } else {
+        text.to_string()
+    }
+}
+
+fn render(
+    records: &[GameRecord],
+    ratings: Option<&ChatRatings>,
+    seasons: Option<&ChatSeasons>,
+    board: Board,
+    period: Period,
+    page: usize,
+    language: Language,
+) -> Option<(String, telegram_types::ReplyMarkup)> {
+    let (range, ratings) = match period {
+        Period::Season(number) => {
+            let season = seasons?.get(number)?;
+            (season.range(), Some(&season.ratings))
+        }
+        _ => ((period.start(), u64::MAX), ratings),
+    };
+    let entries = get_entries(records, ratings, board, range);
+    let page_count = entries.len().div_ceil(PAGE_SIZE).max(1);
+    let page = page.min(page_count - 1);
+    let lines = entries
+        .iter()
+        .enumerate()
+        .skip(page * PAGE_SIZE)
+        .take(PAGE_SIZE)
+        .fold("".to_string(), |res, (i, entry)| {
+            let line = match board {
+                Board::Wins => wins_entry(language, &entry.name, entry.wins, entry.games),
+                Board::WinRate => win_rate_entry(
+                    language,
+                    &entry.name,
+                    entry.win_rate() * 100.0,
+                    entry.wins,
+                    entry.games,
+                ),
+                Board::Rating => rating_entry(language, &entry.name, entry.rating, entry.games),
+            };
+            format!("{}\n{}. {}", res, i + 1, line)
+        });
+    let text = if entries.is_empty() {
+        format!(
+            "{} ({})\n{}",
+            board_title(language, board),
+            period_title(language, period),
+            no_ranked_players(language)
+        )
+    } else {
+        format!(
+            "{} ({}){}",
+            board_title(language, board),
+            period_title(language, period),
+            lines
+        )
+    };
+
+    let mut periods: Vec<Period> = Period::ALL.to_vec();
+    if let Period::Season(_) = period {
+        periods.push(period);
+    }
+    let mut keyboard = vec![
+        Board::ALL
+            .iter()
+            .map(|option| {
+                button(
+                    selected(&board_title(language, *option), *option == board),
+                    *option,
+                    period,
+                    0,
+                )
+            })
+            .collect(),
+        periods
+            .iter()
+            .map(|option| {
+                button(
+                    selected(&period_title(language, *option), *option == period),
+                    board,
+                    *option,
+                    0,
+                )
+            })
+            .collect(),
+    ];
+    let mut paging = vec![];
+    if page > 0 {
+        paging.push(button(
+            previous_page(language).to_string(),
+            board,
+            period,
+            page - 1,
+        ));
+    }
+    if page + 1 < page_count {
+        paging.push(button(
+            next_page(language).to_string(),
+            board,
+            period,
+            page + 1,
+        ));
+    }
+    if !paging.is_empty() {
+        keyboard.push(paging);
+    }
+    Some((
+        text,
+        telegram_types::ReplyMarkup {
+            inline_keyboard: Some(keyboard),
+        },
+    ))
+}
+
+pub fn record(
+    transaction: &mut storage::Transaction,
+    chat_id: telegram_types::ChatId,
+    finished_game: &game_model::FinishedGame,
+) {
+    transaction.add_record(
+        chat_id,
+        GameRecord::from(finished_game, game_model::unix_now()),
+    );
+}
+
+pub fn is_top_callback(data: &str) -> bool {
+    data.starts_with(&format!("{}:", CALLBACK_PREFIX))
+}
+
+fn get_requested_period(args: &[String], seasons: Option<&ChatSeasons>) -> Period {
+    let mut args = args.iter().map(String::as_str);
+    match args.next() {
+        Some("season") => Period::Season(
+            args.next()
+                .and_then(|number| number.parse().ok())
+                .or(seasons.and_then(ChatSeasons::current_number))
+                .unwrap_or_default(),
+        ),
+        _ => Period::AllTime,
+    }
+}
+
+pub fn handle_command(
+    transaction: &storage::Transaction,
+    message: &telegram_types::Message,
+    args: &[String],
+    language: Language,
+) -> message_action::MessageAction {
+    let records = transaction.records(message.chat.id);
+    let ratings = transaction.chat_ratings(message.chat.id);
+    let seasons = transaction.seasons(message.chat.id);
+    let period = get_requested_period(args, seasons.as_ref());
+    let (text, reply_markup) = match render(
+        &records,
+        ratings.as_ref(),
+        seasons.as_ref(),
+        Board::Wins,
+        period,
+        0,
+        language,
+    ) {
+        Some((text, reply_markup)) => (text, Some(reply_markup)),
+        None => (season_not_found(language).to_string(), None),
+    };
+    message_action::MessageAction::Send(message_action::MessageInfo {
+        text,
+        reply_to_message_id: Some(message.message_id),
+        reply_markup,
+        hint: None,
+        is_premium: false,
+        parse_mode: None,
+    })
+}
+
+pub fn handle_callback_query(
+    transaction: &storage::Transaction,
+    message: &telegram_types::Message,
+    data: &str,
+    language: Language,
+) -> Vec<message_action::MessageAction> {
+    let mut parts = data.split(':').skip(1);
+    let (Some(board), Some(period), Some(page)) = (parts.next(), parts.next(), parts.next()) else {
+        return vec![];
+    };
+    let Some(board) = Board::ALL.into_iter().find(|option| option.key() == board) else {
+        return vec![];
+    };
+    let Some(period) = Period::from_key(period) else {
+        return vec![];
+    };
+    let Ok(page) = page.parse::<usize>() else {
+        return vec![];
+    };
+    let records = transaction.records(message.chat.id);
+    let ratings = transaction.chat_ratings(message.chat.id);
+    let seasons = transaction.seasons(message.chat.id);
+    let Some((text, reply_markup)) = render(
+        &records,
+        ratings.as_ref(),
+        seasons.as_ref(),
+        board,
+        period,
+        page,
+        language,
+    ) else {
+        return vec![];
+    };
+    vec![message_action::MessageAction::Edit(
+        message_action::EditMessageInfo {
+            message_id: message.message_id,
+            message_info: message_action::MessageInfo {
+                text,
+                reply_to_message_id: None,
+                reply_markup: Some(reply_markup),
+                hint: None,
+                is_premium: false,
+                parse_mode: None,
+            },
+        },
+    )]
+}
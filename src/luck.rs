@@ -0,0 +1,113 @@
+use super::game_model;
+use super::message_action;
+use super::storage;
+use super::telegram_types;
+use crate::prompt_messages::{
+    luck_current_game_title, luck_lifetime_title, luck_no_rolls, luck_summary, luck_verdict,
+};
+use crate::settings::Language;
+
+const FACES: [&str; 6] = ["⚀", "⚁", "⚂", "⚃", "⚄", "⚅"];
+const BAR_WIDTH: u32 = 10;
+// Chi-square needs at least five expected hits per face to mean anything.
+const MIN_ROLLS_FOR_CHI_SQUARE: u32 = 30;
+// Critical chi-square value for 5 degrees of freedom at the 1% level.
+const CHI_SQUARE_CRITICAL: f64 = 15.09;
+
+fn chi_square(faces: &[u32; 6], rolls: u32) -> f64 {
+    let expected = rolls as f64 / 6.0;
+    faces
+        .iter()
+        .map(|count| (*count as f64 - expected).powi(2) / expected)
+        .sum()
+}
+
+// Number of standard deviations the ones count is below the fair expectation,
+// so a positive index means the player rolled fewer ones than a fair die would.
+fn luck_index(ones: u32, rolls: u32) -> f64 {
+    let expected = rolls as f64 / 6.0;
+    let deviation = (rolls as f64 * 5.0 / 36.0).sqrt();
+    (expected - ones as f64) / deviation
+}
+
+fn histogram(faces: &[u32; 6]) -> String {
+    let max = faces.iter().copied().max().unwrap_or_default().max(1);
+    faces
+        .iter()
+        .zip(FACES)
+        .map(|(count, face)| {
+            format!(
+                "{} {} {}",
+                face,
+                "█".repeat((count * BAR_WIDTH).div_ceil(max) as usize),
+                count
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn describe(language: Language, name: &String, faces: &[u32; 6]) -> String {
+    let rolls: u32 = faces.iter().sum();
+    if rolls == 0 {
+        return luck_no_rolls(language, name);
+    }
+    let chi_square = chi_square(faces, rolls);
+    let luck_index = luck_index(faces[0], rolls);
+    format!(
+        "{}\n{}\n{}",
+        luck_summary(
+            language,
+            name,
+            rolls,
+            faces[0] as f64 * 100.0 / rolls as f64,
+            chi_square,
+            luck_index
+        ),
+        histogram(faces),
+        luck_verdict(
+            language,
+            luck_index,
+            rolls >= MIN_ROLLS_FOR_CHI_SQUARE && chi_square > CHI_SQUARE_CRITICAL
+        )
+    )
+}
+
+pub fn handle_command(
+    transaction: &storage::Transaction,
+    tallies: Vec<(String, game_model::PlayerTally)>,
+    message: &telegram_types::Message,
+    language: Language,
+) -> Option<message_action::MessageAction> {
+    let user = message
+        .reply_to_message
+        .as_ref()
+        .and_then(|replied| replied.from.as_ref())
+        .or(message.from.as_ref())?;
+    let mut sections = vec![];
+    if !tallies.is_empty() {
+        sections.push(tallies.iter().fold(
+            luck_current_game_title(language).to_string(),
+            |res, (name, tally)| format!("{}\n\n{}", res, describe(language, name, &tally.faces)),
+        ));
+    }
+    let faces = transaction
+        .stats(user.id)
+        .map(|stats| stats.faces)
+        .unwrap_or_default();
+    sections.push(format!(
+        "{}\n\n{}",
+        luck_lifetime_title(language, &user.first_name),
+        describe(language, &user.first_name, &faces)
+    ));
+    Some(message_action::MessageAction::Send(
+        message_action::MessageInfo {
+            text: sections.join("\n\n"),
+            reply_to_message_id: Some(message.message_id),
+            reply_markup: None,
+            hint: None,
+            is_premium: false,
+            parse_mode: None,
+        },
+    ))
+}
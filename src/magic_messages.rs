@@ -86,9 +86,7 @@ pub async fn magic(
     };
     let stream_response = submit(request).await?;
     Ok(stream_response.then(|response| async move {
-        let Some(choice) = response.choices.first() else {
-            return None;
-        };
+        let choice = response.choices.first()?;
         let Some(content) = &choice.delta.content else {
             return None;
         };
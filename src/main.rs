@@ -1,19 +1,193 @@
-use axum::{extract::State, routing::post, Json, Router};
-use dashmap::{mapref::entry::Entry, DashMap};
-use prompt_messages::{greeting, greeting_hint};
-use std::sync::Arc;
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::Response,
+    routing::post,
+    Json, Router,
+};
+use command::Command;
+use prompt_messages::{greeting, greeting_hint, settings_admin_only};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use telegram_client::TelegramClient;
 
+mod achievements;
+mod bot_identity;
+mod chat_queue;
+mod command;
+#[cfg(test)]
+mod e2e_tests;
 mod game_model;
+mod journal;
+mod leaderboard;
+mod luck;
 mod magic_messages;
 mod message_action;
+#[cfg(test)]
+mod mock_telegram;
+mod polling;
 mod premium;
 mod prompt_messages;
+mod rate_limiter;
+mod ratings;
+mod seasons;
+mod settings;
+mod snapshot;
+mod sqlite_storage;
+mod stats;
+mod storage;
+mod strategy;
+mod telegram_client;
 mod telegram_types;
 mod text_messages;
+mod update_window;
 
-type GameStateStorage = Arc<DashMap<telegram_types::ChatId, game_model::GameState>>;
+const DEFAULT_DATABASE_FILE: &str = "piggame.db";
+const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+const SECRET_TOKEN_HEADER: &str = "X-Telegram-Bot-Api-Secret-Token";
 
-async fn handle_private_message(message: telegram_types::Message) {
+static REJECTED_UPDATES: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone)]
+struct AppState {
+    storage: Arc<dyn storage::Storage>,
+    recent_updates: Arc<update_window::UpdateWindow>,
+    chat_queues: Arc<chat_queue::ChatQueues>,
+    telegram: Arc<TelegramClient>,
+}
+
+impl AppState {
+    fn new(storage: Arc<dyn storage::Storage>, telegram: TelegramClient) -> AppState {
+        AppState {
+            storage,
+            recent_updates: Arc::new(update_window::UpdateWindow::default()),
+            chat_queues: Arc::new(chat_queue::ChatQueues::new()),
+            telegram: Arc::new(telegram),
+        }
+    }
+
+    // Storage does blocking disk I/O, so transactions run off the async workers.
+    // Nothing is returned for a transaction that was not stored, so no replies
+    // are sent for changes that were rolled back.
+    async fn transaction<R: Default + Send + 'static>(
+        &self,
+        update_id: Option<telegram_types::UpdateId>,
+        update: impl FnOnce(&mut storage::Transaction) -> R + Send + 'static,
+    ) -> R {
+        let storage = self.storage.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let mut update = Some(update);
+            let mut result = R::default();
+            let is_stored = storage.transaction(update_id, &mut |transaction| {
+                if let Some(update) = update.take() {
+                    result = update(transaction);
+                }
+            });
+            if is_stored {
+                result
+            } else {
+                R::default()
+            }
+        })
+        .await;
+        result.unwrap_or_else(|err| {
+            tracing::error!("Storage transaction failed, error: {}", err);
+            R::default()
+        })
+    }
+}
+
+fn update_game(
+    transaction: &mut storage::Transaction,
+    chat_id: telegram_types::ChatId,
+    update: impl FnOnce(&mut game_model::GameState) -> Vec<message_action::MessageAction>,
+) -> Vec<message_action::MessageAction> {
+    let settings = transaction.settings(chat_id);
+    let game = transaction.game_or_new(chat_id);
+    game.touch(game_model::unix_now());
+    let mut actions = update(game);
+    let events = game.take_events();
+    actions.extend(record_events(transaction, chat_id, &settings, events));
+    actions
+}
+
+fn record_events(
+    transaction: &mut storage::Transaction,
+    chat_id: telegram_types::ChatId,
+    settings: &settings::ChatSettings,
+    events: Vec<game_model::GameEvent>,
+) -> Vec<message_action::MessageAction> {
+    let mut actions = vec![];
+    for event in events {
+        actions.extend(achievements::record(transaction, &event, settings.language));
+        if let game_model::GameEvent::Finished(finished_game) = event {
+            stats::record(transaction, &finished_game);
+            leaderboard::record(transaction, chat_id, &finished_game);
+            ratings::record(transaction, chat_id, &finished_game);
+            seasons::record(transaction, chat_id, settings, &finished_game);
+            if settings.verbosity != settings::Verbosity::Quiet {
+                actions.extend(strategy::report(&finished_game, settings.language));
+            }
+        }
+    }
+    actions
+}
+
+async fn handle_private_message(
+    update_id: telegram_types::UpdateId,
+    message: telegram_types::Message,
+    state: AppState,
+) {
+    let chat_id = message.chat.id;
+    let commands = message.get_commands();
+    if commands
+        .iter()
+        .any(|parsed| parsed.command == Command::Luck)
+    {
+        let action = state
+            .transaction(Some(update_id), move |transaction| {
+                luck::handle_command(transaction, vec![], &message, settings::Language::English)
+            })
+            .await;
+        if let Some(action) = action {
+            message_action::send(&state.telegram, chat_id, action).await;
+        }
+        return;
+    }
+    if commands
+        .iter()
+        .any(|parsed| parsed.command == Command::Profile)
+    {
+        let action = state
+            .transaction(Some(update_id), move |transaction| {
+                stats::handle_profile_command(transaction, &message, settings::Language::English)
+            })
+            .await;
+        if let Some(action) = action {
+            message_action::send(&state.telegram, chat_id, action).await;
+        }
+        return;
+    }
+    if commands
+        .iter()
+        .any(|parsed| parsed.command == Command::Stats)
+    {
+        let action = state
+            .transaction(Some(update_id), move |transaction| {
+                stats::handle_command(transaction, &message, settings::Language::English)
+            })
+            .await;
+        if let Some(action) = action {
+            message_action::send(&state.telegram, chat_id, action).await;
+        }
+        return;
+    }
     let (hint, is_premium) = match message.from {
         Some(sender) => (
             Some(greeting_hint(&sender.first_name)),
@@ -21,83 +195,456 @@ async fn handle_private_message(message: telegram_types::Message) {
         ),
         None => (None, false),
     };
+    // Polled updates can be fetched again after a restart, so the greeting is
+    // marked as applied like every other update before it is sent.
+    if !state.transaction(Some(update_id), |_| true).await {
+        return;
+    }
     message_action::send(
-        message.chat.id,
+        &state.telegram,
+        chat_id,
         message_action::MessageAction::Send(message_action::MessageInfo {
             text: greeting().to_owned(),
             reply_to_message_id: None,
             reply_markup: None,
             hint,
             is_premium,
+            parse_mode: None,
         }),
     )
     .await;
 }
 
-async fn handle_group_message(message: telegram_types::Message, storage: GameStateStorage) {
+fn process_group_message(
+    transaction: &mut storage::Transaction,
+    message: &telegram_types::Message,
+    commands: Vec<command::ParsedCommand>,
+    is_admin: bool,
+) -> Vec<message_action::MessageAction> {
+    let chat_id = message.chat.id;
+    let settings = transaction.settings(chat_id);
+    let chat_ratings = transaction.chat_ratings(chat_id);
     let mut actions = vec![];
-    {
-        let mut game = storage
-            .entry(message.chat.id)
-            .or_insert(game_model::GameState::New(game_model::NewGame::new()));
-
-        match message.dice {
-            None => {
-                for command in message.get_commands() {
-                    actions.extend(game.handle_command(&message, command.as_str()));
+    match message.dice {
+        None => {
+            for parsed in commands {
+                match parsed.command {
+                    Command::Settings => {
+                        actions.push(settings::handle_command(&settings, message));
+                    }
+                    Command::Top => {
+                        actions.push(leaderboard::handle_command(
+                            transaction,
+                            message,
+                            &parsed.args,
+                            settings.language,
+                        ));
+                    }
+                    Command::Season => {
+                        actions.push(seasons::handle_command(
+                            transaction,
+                            &settings,
+                            message,
+                            &parsed.args,
+                            is_admin,
+                        ));
+                    }
+                    Command::Stats => {
+                        actions.extend(stats::handle_command(
+                            transaction,
+                            message,
+                            settings.language,
+                        ));
+                    }
+                    Command::Luck => {
+                        let tallies = transaction
+                            .game(chat_id)
+                            .map(|game| game.get_tallies())
+                            .unwrap_or_default();
+                        actions.extend(luck::handle_command(
+                            transaction,
+                            tallies,
+                            message,
+                            settings.language,
+                        ));
+                    }
+                    Command::Profile => {
+                        actions.extend(stats::handle_profile_command(
+                            transaction,
+                            message,
+                            settings.language,
+                        ));
+                    }
+                    Command::Sub => {
+                        actions.extend(update_game(transaction, chat_id, |game| {
+                            game.handle_substitute_command(message, is_admin)
+                        }));
+                    }
+                    command => {
+                        actions.extend(update_game(transaction, chat_id, |game| {
+                            game.handle_command(message, command, &settings, chat_ratings.as_ref())
+                        }));
+                    }
                 }
             }
-            Some(ref dice) => {
-                if matches!(dice.emoji, telegram_types::DiceType::Dice)
-                    && message.forward_date.is_none()
-                {
-                    actions.extend(game.handle_dice(&message, dice.value as u8));
-                };
+        }
+        Some(ref dice) => {
+            if matches!(dice.emoji, telegram_types::DiceType::Dice)
+                && message.forward_date.is_none()
+            {
+                actions.extend(update_game(transaction, chat_id, |game| {
+                    game.handle_dice(message, dice.value as u8, &settings)
+                }));
+            };
+        }
+    };
+    actions
+}
+
+async fn handle_group_message(
+    update_id: telegram_types::UpdateId,
+    message: telegram_types::Message,
+    state: AppState,
+) {
+    let commands = message.get_commands();
+    let needs_admin_check = commands
+        .iter()
+        .any(|parsed| matches!(parsed.command, Command::Sub | Command::Season));
+    let is_admin = match &message.from {
+        Some(sender) if needs_admin_check => {
+            state
+                .telegram
+                .is_chat_admin(message.chat.id, sender.id)
+                .await
+        }
+        _ => false,
+    };
+    let chat_id = message.chat.id;
+    let actions = state
+        .transaction(Some(update_id), move |transaction| {
+            process_group_message(transaction, &message, commands, is_admin)
+        })
+        .await;
+    for action in actions {
+        message_action::send(&state.telegram, chat_id, action).await;
+    }
+}
+
+fn process_callback_query(
+    transaction: &mut storage::Transaction,
+    message: &telegram_types::Message,
+    from: &telegram_types::User,
+    data: Option<String>,
+) -> Vec<message_action::MessageAction> {
+    let chat_id = message.chat.id;
+    let settings = transaction.settings(chat_id);
+    match data {
+        Some(data) if settings::is_settings_callback(&data) => {
+            let chat_settings = transaction.settings_mut(chat_id);
+            let actions = settings::handle_callback_query(chat_settings, message, &data);
+            let chat_settings = *chat_settings;
+            if let Some(game) = transaction.game_mut(chat_id) {
+                game.update_settings(chat_settings);
             }
-        };
+            actions
+        }
+        Some(data) if leaderboard::is_top_callback(&data) => {
+            leaderboard::handle_callback_query(transaction, message, &data, settings.language)
+        }
+        data => update_game(transaction, chat_id, |game| {
+            game.handle_callback_query(message, from, data, &settings)
+        }),
     }
+}
 
+async fn handle_callback_query(
+    update_id: telegram_types::UpdateId,
+    callback_query: telegram_types::CallbackQuery,
+    state: AppState,
+) {
+    let Some(message) = callback_query.message else {
+        state
+            .telegram
+            .answer_callback_query(&callback_query.id, None)
+            .await;
+        return;
+    };
+    let chat_id = message.chat.id;
+    let from = callback_query.from;
+    let data = callback_query.data;
+    // The settings menu is shared by the whole chat, so like /season new and
+    // /sub for another seat it is only for admins.
+    if data.as_deref().is_some_and(settings::is_settings_callback)
+        && !state.telegram.is_chat_admin(chat_id, from.id).await
+    {
+        let chat_settings = state
+            .transaction(Some(update_id), move |transaction| {
+                transaction.settings(chat_id)
+            })
+            .await;
+        state
+            .telegram
+            .answer_callback_query(
+                &callback_query.id,
+                Some(settings_admin_only(chat_settings.language)),
+            )
+            .await;
+        return;
+    }
+    state
+        .telegram
+        .answer_callback_query(&callback_query.id, None)
+        .await;
+    let actions = state
+        .transaction(Some(update_id), move |transaction| {
+            process_callback_query(transaction, &message, &from, data)
+        })
+        .await;
     for action in actions {
-        message_action::send(message.chat.id, action).await;
+        message_action::send(&state.telegram, chat_id, action).await;
     }
 }
 
-async fn handle(
-    State(storage): State<GameStateStorage>,
-    Json(update): Json<telegram_types::Update>,
-) {
-    if let Some(message) = update.message {
+// Compares every byte so the time taken does not reveal how much of the token matched.
+fn is_same_token(expected: &[u8], actual: &[u8]) -> bool {
+    expected.len() == actual.len()
+        && expected
+            .iter()
+            .zip(actual)
+            .fold(0, |diff, (expected, actual)| diff | (expected ^ actual))
+            == 0
+}
+
+async fn verify_secret_token(
+    State(secret_token): State<Option<Arc<String>>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let Some(secret_token) = secret_token else {
+        return Ok(next.run(request).await);
+    };
+    let actual = request
+        .headers()
+        .get(SECRET_TOKEN_HEADER)
+        .map(|value| value.as_bytes())
+        .unwrap_or_default();
+    if !is_same_token(secret_token.as_bytes(), actual) {
+        let rejected = REJECTED_UPDATES.fetch_add(1, Ordering::Relaxed) + 1;
+        tracing::warn!(
+            "Rejected an update with a wrong secret token, {} rejected so far",
+            rejected
+        );
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(next.run(request).await)
+}
+
+async fn handle(State(state): State<AppState>, Json(update): Json<telegram_types::Update>) {
+    chat_queue::push(state, update, None);
+}
+
+fn router(state: AppState, secret_token: Option<Arc<String>>) -> Router {
+    Router::new()
+        .route("/", post(handle))
+        .layer(middleware::from_fn_with_state(
+            secret_token,
+            verify_secret_token,
+        ))
+        .with_state(state)
+}
+
+async fn process_update(state: AppState, update: telegram_types::Update) {
+    if !state.recent_updates.insert(update.update_id) {
+        tracing::info!("Skipped a duplicate delivery of an update");
+        return;
+    }
+    let update_id = update.update_id;
+    let storage = state.storage.clone();
+    if tokio::task::spawn_blocking(move || storage.is_applied(update_id))
+        .await
+        .unwrap_or_default()
+    {
+        return;
+    }
+    if let Some((from, to)) = update
+        .message
+        .as_ref()
+        .and_then(|message| message.get_migration())
+    {
+        state
+            .transaction(Some(update_id), move |transaction| {
+                transaction.migrate_chat(from, to)
+            })
+            .await;
+    } else if let Some(message) = update.message {
         match message.chat.chat_type {
             telegram_types::ChatType::Group | telegram_types::ChatType::SuperGroup => {
-                handle_group_message(message, storage).await
+                handle_group_message(update_id, message, state).await
+            }
+            telegram_types::ChatType::Private => {
+                handle_private_message(update_id, message, state).await
             }
-            telegram_types::ChatType::Private => handle_private_message(message).await,
             _ => (),
         }
     } else if let Some(callback_query) = update.callback_query {
-        if let Some(message) = callback_query.message {
-            let actions = match storage.entry(message.chat.id) {
-                Entry::Occupied(mut occupied) => {
-                    let game = occupied.get_mut();
-                    game.handle_callback_query(&message, callback_query.data)
-                }
-                Entry::Vacant(_) => vec![],
-            };
-            for action in actions {
-                message_action::send(message.chat.id, action).await;
-            }
-        };
+        handle_callback_query(update_id, callback_query, state).await;
     };
 }
 
+fn check_game_deadline(
+    transaction: &mut storage::Transaction,
+    chat_id: telegram_types::ChatId,
+    now: u64,
+) -> Vec<message_action::MessageAction> {
+    let Some(game) = transaction.game_mut(chat_id) else {
+        return vec![];
+    };
+    let mut actions = game.check_turn_timeout(now);
+    match game.check_idle(now) {
+        game_model::IdleCheck::Active => (),
+        game_model::IdleCheck::Warned(action) => actions.push(action),
+        game_model::IdleCheck::Expired(expired_actions) => {
+            actions.extend(expired_actions);
+            transaction.remove_game(chat_id);
+        }
+    }
+    actions
+}
+
+async fn check_deadline(state: AppState, chat_id: telegram_types::ChatId) {
+    let now = game_model::unix_now();
+    let actions = state
+        .transaction(None, move |transaction| {
+            check_game_deadline(transaction, chat_id, now)
+        })
+        .await;
+    for action in actions {
+        message_action::send(&state.telegram, chat_id, action).await;
+    }
+}
+
+async fn check_season(state: AppState, chat_id: telegram_types::ChatId) {
+    let action = state
+        .transaction(None, move |transaction| {
+            seasons::check_rollover(transaction, chat_id)
+        })
+        .await;
+    if let Some(action) = action {
+        message_action::send(&state.telegram, chat_id, action).await;
+    }
+}
+
+// The checks go through the chat queues, so they run next to the chat's updates.
+async fn check_deadlines(state: AppState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(5));
+    loop {
+        interval.tick().await;
+        let now = game_model::unix_now();
+        let storage = state.storage.clone();
+        let chat_ids = tokio::task::spawn_blocking(move || storage.get_due_chats(now))
+            .await
+            .unwrap_or_default();
+        for chat_id in chat_ids {
+            chat_queue::push_job(state.clone(), chat_id, chat_queue::Job::CheckDeadline);
+        }
+    }
+}
+
+async fn check_season_rollovers(state: AppState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        let month_start = leaderboard::Period::Month.start();
+        let storage = state.storage.clone();
+        let chat_ids = tokio::task::spawn_blocking(move || storage.get_season_chats(month_start))
+            .await
+            .unwrap_or_default();
+        for chat_id in chat_ids {
+            chat_queue::push_job(state.clone(), chat_id, chat_queue::Job::CheckSeason);
+        }
+    }
+}
+
+async fn shutdown_signal() {
+    let mut terminate =
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).unwrap();
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => (),
+        _ = terminate.recv() => (),
+    }
+}
+
+// SQLite keeps every change on disk, so memory storage with its snapshot and
+// journal has to be asked for explicitly.
+fn open_storage() -> (
+    Arc<dyn storage::Storage>,
+    Option<Arc<storage::InMemoryStorage>>,
+) {
+    if std::env::var("STORAGE").as_deref() == Ok("memory") {
+        let (data, journal) =
+            journal::Journal::open(journal::get_journal_path(), snapshot::get_snapshot_path())
+                .expect("Journal file can not be opened");
+        let storage = Arc::new(storage::InMemoryStorage::new(data, Some(journal)));
+        return (storage.clone(), Some(storage));
+    }
+    let storage = sqlite_storage::SqliteStorage::open(
+        std::env::var("DATABASE_FILE").unwrap_or(DEFAULT_DATABASE_FILE.to_string()),
+    )
+    .expect("SQLite database can not be opened");
+    (Arc::new(storage), None)
+}
+
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt::init();
-    let storage = GameStateStorage::new(DashMap::new());
+    let (storage, memory_storage) = open_storage();
+    if let Some(usernames) = premium::load_usernames() {
+        storage.set_premium_usernames(&usernames);
+    }
+    premium::init(storage.get_premium_usernames());
+    let state = AppState::new(storage, TelegramClient::from_env());
 
-    let app = Router::new().route("/", post(handle)).with_state(storage);
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:32926")
-        .await
-        .unwrap();
-    axum::serve(listener, app).await.unwrap();
+    tokio::spawn(check_deadlines(state.clone()));
+    if let Some(memory_storage) = &memory_storage {
+        tokio::spawn(snapshot::run(memory_storage.clone()));
+    }
+    tokio::spawn(check_season_rollovers(state.clone()));
+    tokio::task::spawn_blocking(strategy::warm_up);
+
+    bot_identity::init(&state.telegram).await;
+    let chat_queues = state.chat_queues.clone();
+    if std::env::var("UPDATE_MODE").as_deref() == Ok("polling") {
+        tokio::select! {
+            _ = polling::run(state) => (),
+            _ = shutdown_signal() => (),
+        }
+    } else {
+        let secret_token = std::env::var("WEBHOOK_SECRET").ok().map(Arc::new);
+        if secret_token.is_none() {
+            tracing::warn!("WEBHOOK_SECRET is not set, updates are accepted from anyone");
+        }
+        if let Ok(url) = std::env::var("WEBHOOK_URL") {
+            if !state
+                .telegram
+                .set_webhook(&url, secret_token.as_deref().map(String::as_str))
+                .await
+            {
+                tracing::error!("Can not register webhook {}", url);
+            }
+        }
+        let app = router(state, secret_token);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:32926")
+            .await
+            .unwrap();
+        axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown_signal())
+            .await
+            .unwrap();
+    }
+    chat_queue::drain(&chat_queues, SHUTDOWN_DRAIN_TIMEOUT).await;
+    if let Some(memory_storage) = memory_storage {
+        if let Err(err) = memory_storage.compact() {
+            tracing::error!("Can not write snapshot on shutdown, error: {}", err);
+        }
+    }
 }
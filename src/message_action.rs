@@ -1,9 +1,16 @@
-use std::{pin::pin, sync::OnceLock};
+use std::pin::pin;
 
+use super::telegram_client::TelegramClient;
 use super::telegram_types;
 use futures::StreamExt;
 use serde::Serialize;
 
+#[derive(Serialize, Clone, Copy)]
+pub enum ParseMode {
+    #[serde(rename = "HTML")]
+    Html,
+}
+
 #[derive(Serialize)]
 pub struct MessageInfo {
     pub text: String,
@@ -11,6 +18,8 @@ pub struct MessageInfo {
     pub reply_to_message_id: Option<telegram_types::MessageId>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reply_markup: Option<telegram_types::ReplyMarkup>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parse_mode: Option<ParseMode>,
     #[serde(skip_serializing)]
     pub hint: Option<String>,
     #[serde(skip_serializing)]
@@ -29,23 +38,13 @@ pub enum MessageAction {
     Edit(EditMessageInfo),
 }
 
-#[derive(Serialize)]
-struct ChatMessage<Info: Serialize> {
-    chat_id: telegram_types::ChatId,
-    #[serde(flatten)]
-    info: Info,
-}
-
 async fn send_stream(
-    client: &reqwest::Client,
-    bot_token: &String,
+    telegram: &TelegramClient,
     info: MessageInfo,
     chat_id: telegram_types::ChatId,
     mut message_id: Option<telegram_types::MessageId>,
 ) {
-    if let Some(new_message_id) =
-        append_chunk(message_id, client, bot_token, chat_id, "...", &info).await
-    {
+    if let Some(new_message_id) = append_chunk(message_id, telegram, chat_id, "...", &info).await {
         message_id = Some(new_message_id);
     } else {
         return;
@@ -66,7 +65,7 @@ async fn send_stream(
                 }
                 added_len = 0;
                 if let Some(new_message_id) =
-                    append_chunk(message_id, client, bot_token, chat_id, &text, &info).await
+                    append_chunk(message_id, telegram, chat_id, &text, &info).await
                 {
                     message_id = Some(new_message_id);
                 } else {
@@ -76,7 +75,7 @@ async fn send_stream(
             if added_len == 0 {
                 return;
             }
-            append_chunk(message_id, client, bot_token, chat_id, &text, &info).await;
+            append_chunk(message_id, telegram, chat_id, &text, &info).await;
         }
         Err(err) => {
             tracing::error!("Failed to call OpenAI API, error: {}", err);
@@ -86,145 +85,55 @@ async fn send_stream(
 
 async fn append_chunk(
     message_id: Option<telegram_types::MessageId>,
-    client: &reqwest::Client,
-    bot_token: &String,
+    telegram: &TelegramClient,
     chat_id: telegram_types::ChatId,
     text: &str,
     info: &MessageInfo,
 ) -> Option<telegram_types::MessageId> {
-    match message_id {
+    let message_info = MessageInfo {
+        text: text.to_owned(),
+        reply_markup: info.reply_markup.clone(),
+        // Generated text is not escaped, so it is always sent as plain text.
+        parse_mode: None,
+        hint: Option::None,
+        ..*info
+    };
+    let result = match message_id {
         Some(message_id) => {
-            let result = get_result(
-                client
-                    .post(format!(
-                        "https://api.telegram.org/bot{}/{}",
-                        bot_token, "editMessageText"
-                    ))
-                    .json(&ChatMessage::<EditMessageInfo> {
-                        chat_id,
-                        info: EditMessageInfo {
-                            message_id,
-                            message_info: MessageInfo {
-                                text: text.to_owned(),
-                                reply_markup: info.reply_markup.clone(),
-                                hint: Option::None,
-                                ..*info
-                            },
-                        },
-                    })
-                    .send()
-                    .await,
-            )
-            .await?;
-            Some(result.result.message_id)
-        }
-        None => {
-            let result = get_result(
-                client
-                    .post(format!(
-                        "https://api.telegram.org/bot{}/{}",
-                        bot_token, "sendMessage"
-                    ))
-                    .json(&ChatMessage::<MessageInfo> {
-                        chat_id,
-                        info: MessageInfo {
-                            text: text.to_owned(),
-                            reply_markup: info.reply_markup.clone(),
-                            hint: Option::None,
-                            ..*info
-                        },
-                    })
-                    .send()
-                    .await,
-            )
-            .await?;
-            Some(result.result.message_id)
+            telegram
+                .edit_message_text(
+                    chat_id,
+                    &EditMessageInfo {
+                        message_id,
+                        message_info,
+                    },
+                )
+                .await?
         }
-    }
+        None => telegram.send_message(chat_id, &message_info).await?,
+    };
+    Some(result.message_id)
 }
 
-pub async fn send(chat_id: telegram_types::ChatId, action: MessageAction) {
-    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
-    static TOKEN: OnceLock<String> = OnceLock::new();
-    let bot_token = TOKEN.get_or_init(|| {
-        std::env::var("BOT_TOKEN").expect("BOT_TOKEN environment variable is not set")
-    });
-    let client = CLIENT.get_or_init(reqwest::Client::new);
-    let result = match action {
+pub async fn send(
+    telegram: &TelegramClient,
+    chat_id: telegram_types::ChatId,
+    action: MessageAction,
+) {
+    match action {
         MessageAction::Send(info) => {
             if info.is_premium {
-                send_stream(client, bot_token, info, chat_id, None).await;
+                send_stream(telegram, info, chat_id, None).await;
                 return;
             }
-            client
-                .post(format!(
-                    "https://api.telegram.org/bot{}/{}",
-                    bot_token, "sendMessage"
-                ))
-                .json(&ChatMessage::<MessageInfo> { chat_id, info })
-                .send()
-                .await
+            telegram.send_message(chat_id, &info).await;
         }
         MessageAction::Edit(info) => {
             if info.message_info.is_premium {
-                send_stream(
-                    client,
-                    bot_token,
-                    info.message_info,
-                    chat_id,
-                    Some(info.message_id),
-                )
-                .await;
+                send_stream(telegram, info.message_info, chat_id, Some(info.message_id)).await;
                 return;
             }
-            client
-                .post(format!(
-                    "https://api.telegram.org/bot{}/{}",
-                    bot_token, "editMessageText"
-                ))
-                .json(&ChatMessage::<EditMessageInfo> { chat_id, info })
-                .send()
-                .await
+            telegram.edit_message_text(chat_id, &info).await;
         }
     };
-
-    handle_api_call(result).await;
-}
-
-async fn handle_api_call(
-    result: Result<reqwest::Response, reqwest::Error>,
-) -> Option<reqwest::Response> {
-    match result {
-        Ok(res) => {
-            if !res.status().is_success() {
-                match res.text().await {
-                    Ok(body) => {
-                        tracing::error!("Telegram API call was not success, {}", body);
-                    }
-                    Err(_) => {
-                        tracing::error!("Telegram API call was not success, can not extract response text neither");
-                    }
-                }
-                return None;
-            }
-            Some(res)
-        }
-        Err(err) => {
-            tracing::error!("Can not send a request to Telegram, error: {}", err);
-            None
-        }
-    }
-}
-
-async fn get_result(
-    response_result: Result<reqwest::Response, reqwest::Error>,
-) -> Option<telegram_types::ResultMessage> {
-    let response = handle_api_call(response_result).await?;
-    match response.json::<telegram_types::ResultMessage>().await {
-        Ok(result) => Some(result),
-        Err(err) => {
-            tracing::error!("Can not parse Telegram response, error: {}", err);
-            None
-        }
-    }
 }
@@ -0,0 +1,114 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::post,
+    Json, Router,
+};
+use serde_json::{json, Value};
+
+pub struct ApiCall {
+    pub method: String,
+    pub body: Value,
+}
+
+#[derive(Clone, Default)]
+struct Recorder {
+    calls: Arc<Mutex<Vec<ApiCall>>>,
+    last_message_id: Arc<Mutex<i64>>,
+    failures: Arc<Mutex<VecDeque<(StatusCode, Value)>>>,
+}
+
+// An in-process stand-in for the Bot API. Every call is recorded and answered
+// with a payload shaped like the real one.
+pub struct MockTelegram {
+    pub url: String,
+    recorder: Recorder,
+}
+
+impl MockTelegram {
+    pub async fn start() -> MockTelegram {
+        let recorder = Recorder::default();
+        let app = Router::new()
+            .route("/:bot/:method", post(handle))
+            .with_state(recorder.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        MockTelegram { url, recorder }
+    }
+
+    // The next calls are answered with these errors, one call each.
+    pub fn fail_next(&self, status: StatusCode, body: Value) {
+        self.recorder
+            .failures
+            .lock()
+            .unwrap()
+            .push_back((status, body));
+    }
+
+    pub fn take_calls(&self) -> Vec<ApiCall> {
+        std::mem::take(&mut self.recorder.calls.lock().unwrap())
+    }
+
+    // Texts of the sent and edited messages since the last call, in order.
+    pub fn take_texts(&self) -> Vec<String> {
+        texts(self.take_calls())
+    }
+}
+
+pub fn texts(calls: Vec<ApiCall>) -> Vec<String> {
+    calls
+        .into_iter()
+        .filter(|call| matches!(call.method.as_str(), "sendMessage" | "editMessageText"))
+        .filter_map(|call| call.body["text"].as_str().map(str::to_string))
+        .collect()
+}
+
+async fn handle(
+    State(recorder): State<Recorder>,
+    Path((_, method)): Path<(String, String)>,
+    Json(body): Json<Value>,
+) -> (StatusCode, Json<Value>) {
+    let failure = recorder.failures.lock().unwrap().pop_front();
+    let result = match method.as_str() {
+        "sendMessage" | "sendDice" | "editMessageText" => {
+            let message_id = match body["message_id"].as_i64() {
+                Some(message_id) => message_id,
+                None => {
+                    let mut last_message_id = recorder.last_message_id.lock().unwrap();
+                    *last_message_id += 1;
+                    *last_message_id
+                }
+            };
+            let chat_id = body["chat_id"].as_i64().unwrap_or_default();
+            json!({
+                "message_id": message_id,
+                "date": 0,
+                "chat": {
+                    "id": chat_id,
+                    "type": if chat_id < 0 { "group" } else { "private" },
+                },
+                "text": body["text"],
+                "dice": body["emoji"].as_str().map(|emoji| json!({"emoji": emoji, "value": 6})),
+            })
+        }
+        "getMe" => json!({"id": 1, "is_bot": true, "first_name": "Pig", "username": "piggamebot"}),
+        "getChatMember" => json!({"status": "member"}),
+        "getUpdates" => json!([]),
+        _ => json!(true),
+    };
+    recorder
+        .calls
+        .lock()
+        .unwrap()
+        .push(ApiCall { method, body });
+    match failure {
+        Some((status, body)) => (status, Json(body)),
+        None => (StatusCode::OK, Json(json!({"ok": true, "result": result}))),
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use tokio::sync::Notify;
+
+use super::chat_queue;
+use super::telegram_types;
+use super::AppState;
+
+const DEFAULT_OFFSET_FILE: &str = "polling_offset";
+const POLLING_TIMEOUT: u64 = 50;
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+const PENDING_WAIT: Duration = Duration::from_secs(1);
+
+fn get_offset_path() -> PathBuf {
+    std::env::var("POLLING_OFFSET_FILE")
+        .unwrap_or(DEFAULT_OFFSET_FILE.to_string())
+        .into()
+}
+
+fn load_offset() -> Option<telegram_types::UpdateId> {
+    let path = get_offset_path();
+    match fs::read(&path) {
+        Ok(content) => match serde_json::from_slice(&content) {
+            Ok(offset) => Some(offset),
+            Err(err) => {
+                tracing::error!("Can not parse {}, error: {}", path.display(), err);
+                None
+            }
+        },
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => {
+            tracing::error!("Can not read {}, error: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+fn save_offset(offset: telegram_types::UpdateId) -> std::io::Result<()> {
+    let path = get_offset_path();
+    let tmp_path = path.with_extension("tmp");
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(&serde_json::to_vec(&offset)?)?;
+    file.sync_all()?;
+    fs::rename(tmp_path, path)
+}
+
+// Updates that were queued but are not processed yet.
+#[derive(Default)]
+struct Pending {
+    update_ids: Mutex<BTreeSet<telegram_types::UpdateId>>,
+    finished: Notify,
+}
+
+impl Pending {
+    fn lock(&self) -> MutexGuard<'_, BTreeSet<telegram_types::UpdateId>> {
+        self.update_ids
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    fn finish(&self, update_id: telegram_types::UpdateId) {
+        self.lock().remove(&update_id);
+        self.finished.notify_one();
+    }
+}
+
+// Telegram forgets every update below the offset it is asked with, so the
+// offset is the lowest update that is not processed yet, and it is saved
+// before it is sent. Updates from there on that were queued already are
+// skipped, and after a restart the ones that were stored are skipped by
+// `process_update`.
+pub async fn run(state: AppState) {
+    if !state.telegram.delete_webhook().await {
+        tracing::error!("Can not delete webhook, polling may be refused");
+    }
+    let pending = Arc::new(Pending::default());
+    let mut saved_offset = load_offset();
+    let mut next_update_id = saved_offset;
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let offset = pending.lock().first().copied().or(next_update_id);
+        if offset != saved_offset {
+            if let Some(offset) = offset {
+                if let Err(err) = save_offset(offset) {
+                    tracing::error!("Can not save polling offset, error: {}", err);
+                }
+            }
+            saved_offset = offset;
+        }
+        let Some(updates) = state.telegram.get_updates(offset, POLLING_TIMEOUT).await else {
+            tracing::warn!("Polling failed, retrying in {} s", backoff.as_secs());
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+            continue;
+        };
+        backoff = MIN_BACKOFF;
+        let mut has_new_updates = false;
+        for update in updates {
+            let update_id = update.update_id;
+            if next_update_id.is_some_and(|next_update_id| update_id < next_update_id) {
+                continue;
+            }
+            next_update_id = Some(update_id.next());
+            has_new_updates = true;
+            pending.lock().insert(update_id);
+            let pending = pending.clone();
+            chat_queue::push(
+                state.clone(),
+                update,
+                Some(Box::new(move || pending.finish(update_id))),
+            );
+        }
+        // Unprocessed updates come back right away, so instead of asking again
+        // at once the loop waits for one of them to finish.
+        if !has_new_updates && !pending.lock().is_empty() {
+            let _ = tokio::time::timeout(PENDING_WAIT, pending.finished.notified()).await;
+        }
+    }
+}
@@ -2,24 +2,30 @@ use std::{collections::HashSet, fs::File, sync::OnceLock};
 
 use serde::Deserialize;
 
+static USERNAMES: OnceLock<HashSet<String>> = OnceLock::new();
+
 #[derive(Deserialize)]
 struct Usernames {
     usernames: Vec<String>,
 }
 
+// Without a file the stored premium users are kept as they are.
+pub fn load_usernames() -> Option<Vec<String>> {
+    let file_path = std::env::args().nth(1)?;
+    let file = File::open(file_path).unwrap();
+    Some(
+        serde_yaml::from_reader::<_, Usernames>(file)
+            .unwrap()
+            .usernames,
+    )
+}
+
+pub fn init(usernames: HashSet<String>) {
+    let _ = USERNAMES.set(usernames);
+}
+
 pub fn is_premium(username: String) -> bool {
-    static USERNAMES: OnceLock<HashSet<String>> = OnceLock::new();
     USERNAMES
-        .get_or_init(|| {
-            let file_path = std::env::args()
-                .nth(1)
-                .expect("Premium users YAML file is not provided!");
-            let file = File::open(file_path).unwrap();
-            serde_yaml::from_reader::<_, Usernames>(file)
-                .unwrap()
-                .usernames
-                .into_iter()
-                .collect()
-        })
-        .contains(&username)
+        .get()
+        .is_some_and(|usernames| usernames.contains(&username))
 }
@@ -1,3 +1,9 @@
+use crate::command::Command;
+use crate::leaderboard::{Board, Period};
+use crate::settings::{Language, LobbyPolicy, SeasonMode, Setting, Variant, Verbosity};
+use crate::stats::UserStats;
+use crate::strategy::RiskProfile;
+
 const DEFAULT_SYSTEM_MESSAGE: &str = "\
     You are a game bot. \
     The game is a Pig dice game. \
@@ -6,6 +12,7 @@ const DEFAULT_SYSTEM_MESSAGE: &str = "\
     Rewrite the user's message, it's what you should say. \
     Mention all the details about the game state. \
     Write up to 3 sentences but keep it short. \
+    Reply in the language of the user's message. \
     Free to use emojis.";
 
 pub fn system_message(extra_info: &Option<String>) -> String {
@@ -23,29 +30,74 @@ pub fn greeting_hint(name: &String) -> String {
     format!("Audience name is {}.", name)
 }
 
-pub const fn already_joined() -> &'static str {
-    "You have joined already, you can't do it again :)"
+pub const fn already_joined(language: Language) -> &'static str {
+    match language {
+        Language::English => "You have joined already, you can't do it again :)",
+        Language::Persian => "شما قبلاً به بازی پیوسته‌اید و نمی‌توانید دوباره بپیوندید :)",
+    }
 }
-pub const fn game_already_started() -> &'static str {
-    "Game is already started :("
+pub const fn game_already_started(language: Language) -> &'static str {
+    match language {
+        Language::English => "Game is already started :(",
+        Language::Persian => "بازی قبلاً شروع شده است :(",
+    }
 }
-pub const fn not_enough_player() -> &'static str {
-    "Not enough players joined yet :("
+pub const fn late_join_disabled(language: Language) -> &'static str {
+    match language {
+        Language::English => "Game is already started and late joining is disabled :(",
+        Language::Persian => "بازی شروع شده و پیوستن دیرهنگام غیرفعال است :(",
+    }
 }
-pub const fn game_is_not_started() -> &'static str {
-    "Game is not started yet :("
+pub const fn not_enough_player(language: Language) -> &'static str {
+    match language {
+        Language::English => "Not enough players joined yet :(",
+        Language::Persian => "هنوز بازیکن کافی به بازی نپیوسته است :(",
+    }
+}
+pub const fn game_is_not_started(language: Language) -> &'static str {
+    match language {
+        Language::English => "Game is not started yet :(",
+        Language::Persian => "بازی هنوز شروع نشده است :(",
+    }
 }
-pub const fn not_your_turn() -> &'static str {
-    "This is not your turn :("
+pub const fn not_your_turn(language: Language) -> &'static str {
+    match language {
+        Language::English => "This is not your turn :(",
+        Language::Persian => "نوبت شما نیست :(",
+    }
 }
-pub const fn not_joined() -> &'static str {
-    "You are not joined the game so you can't leave :("
+pub const fn not_joined(language: Language) -> &'static str {
+    match language {
+        Language::English => "You are not joined the game so you can't leave :(",
+        Language::Persian => "شما در بازی نیستید پس نمی‌توانید آن را ترک کنید :(",
+    }
 }
 
 pub fn game_logic_error_hint(name: &String) -> String {
     format!("Audience name is {}.", name)
 }
 
+pub const fn no_players(language: Language) -> &'static str {
+    match language {
+        Language::English => "No players!",
+        Language::Persian => "بازیکنی وجود ندارد!",
+    }
+}
+
+pub const fn players_title(language: Language) -> &'static str {
+    match language {
+        Language::English => "Players:",
+        Language::Persian => "بازیکنان:",
+    }
+}
+
+pub const fn scores_title(language: Language) -> &'static str {
+    match language {
+        Language::English => "Scores:",
+        Language::Persian => "امتیازها:",
+    }
+}
+
 pub const fn player_list_hint() -> &'static str {
     "\
     List of the players who joined the game provided.\
@@ -62,11 +114,14 @@ pub const fn result_hint() -> &'static str {
     Say your opinion about the current state of the game."
 }
 
-pub const fn turn_lost() -> &'static str {
-    "Oops! You lost your turn :("
+pub const fn turn_lost(language: Language) -> &'static str {
+    match language {
+        Language::English => "Oops! You lost your turn :(",
+        Language::Persian => "اوه! نوبتت را از دست دادی :(",
+    }
 }
 
-pub fn turn_lost_hint(name: &String, last_score: u8) -> String {
+pub fn turn_lost_hint(name: &String, last_score: u16) -> String {
     format!(
         "\
         {} lost the turn after rolling a \"one\" by the dice. \
@@ -78,8 +133,44 @@ pub fn turn_lost_hint(name: &String, last_score: u8) -> String {
     )
 }
 
-pub fn next_turn(player_name: &String) -> String {
-    format!("It's {} turn to roll the dice.", player_name)
+pub const fn score_lost(language: Language) -> &'static str {
+    match language {
+        Language::English => "Two sixes in a row! Your whole score is gone :(",
+        Language::Persian => "دو شش پشت سر هم! کل امتیازت از دست رفت :(",
+    }
+}
+
+pub fn score_lost_hint(name: &String, lost_score: u16) -> String {
+    format!(
+        "\
+        {} rolled two sixes in a row and lost all the {} points \
+        collected in the game so far. \
+        Say how unlucky the player was.",
+        name, lost_score
+    )
+}
+
+pub fn turn_timed_out(language: Language, player_name: &String) -> String {
+    match language {
+        Language::English => format!("{} took too long, the turn is skipped.", player_name),
+        Language::Persian => format!("{} بیش از حد طول کشید، نوبت رد شد.", player_name),
+    }
+}
+
+pub fn turn_timed_out_hint(name: &String, last_score: u16) -> String {
+    format!(
+        "\
+        {} did not roll or hold in time, so the turn is skipped \
+        and {} points of the turn are lost.",
+        name, last_score
+    )
+}
+
+pub fn next_turn(language: Language, player_name: &String) -> String {
+    match language {
+        Language::English => format!("It's {} turn to roll the dice.", player_name),
+        Language::Persian => format!("نوبت {} است که تاس بیندازد.", player_name),
+    }
 }
 
 pub fn next_turn_hint(name: &String) -> String {
@@ -90,19 +181,25 @@ pub fn next_turn_hint(name: &String) -> String {
     )
 }
 
-pub const fn joined() -> &'static str {
-    "You joined the game successfully!"
+pub const fn joined(language: Language) -> &'static str {
+    match language {
+        Language::English => "You joined the game successfully!",
+        Language::Persian => "با موفقیت به بازی پیوستید!",
+    }
 }
 
 pub fn joined_hint(name: &String) -> String {
     format!("{} joined the game.", name)
 }
 
-pub const fn player_left() -> &'static str {
-    "You left the game."
+pub const fn player_left(language: Language) -> &'static str {
+    match language {
+        Language::English => "You left the game.",
+        Language::Persian => "شما بازی را ترک کردید.",
+    }
 }
 
-pub fn player_left_hint(name: &String, score: u8) -> String {
+pub fn player_left_hint(name: &String, score: u16) -> String {
     format!(
         "\
         {} left the game with {} points. \
@@ -111,8 +208,11 @@ pub fn player_left_hint(name: &String, score: u8) -> String {
     )
 }
 
-pub fn started(player_name: &String) -> String {
-    format!("The game has just started. Turn: {}.", player_name)
+pub fn started(language: Language, player_name: &String) -> String {
+    match language {
+        Language::English => format!("The game has just started. Turn: {}.", player_name),
+        Language::Persian => format!("بازی همین الان شروع شد. نوبت: {}.", player_name),
+    }
 }
 
 pub fn started_hint(name: &String) -> String {
@@ -124,11 +224,14 @@ pub fn started_hint(name: &String) -> String {
     )
 }
 
-pub fn hold(score: u8, next_player: &String) -> String {
-    format!("Your total score is {}. Next turn: {}", score, next_player)
+pub fn hold(language: Language, score: u16, next_player: &String) -> String {
+    match language {
+        Language::English => format!("Your total score is {}. Next turn: {}", score, next_player),
+        Language::Persian => format!("امتیاز کل شما {} است. نوبت بعدی: {}", score, next_player),
+    }
 }
 
-pub fn hold_hint(name: &String, turn_score: u8, total_score: u8) -> String {
+pub fn hold_hint(name: &String, turn_score: u16, total_score: u16) -> String {
     format!(
         "\
         {} decided to hold their achieved points and pass the dice \
@@ -140,8 +243,18 @@ pub fn hold_hint(name: &String, turn_score: u8, total_score: u8) -> String {
     )
 }
 
-pub const fn reset_confirm() -> &'static str {
-    "Are you sure?"
+pub const fn reset_confirm(language: Language) -> &'static str {
+    match language {
+        Language::English => "Are you sure?",
+        Language::Persian => "مطمئن هستید؟",
+    }
+}
+
+pub const fn yes(language: Language) -> &'static str {
+    match language {
+        Language::English => "Yes",
+        Language::Persian => "بله",
+    }
 }
 
 pub fn reset_confirm_hint(name: &String) -> String {
@@ -152,14 +265,620 @@ pub fn reset_confirm_hint(name: &String) -> String {
     )
 }
 
-pub const fn reset() -> &'static str {
-    "Game is reset (players should join again)."
+pub const fn reset(language: Language) -> &'static str {
+    match language {
+        Language::English => "Game is reset (players should join again).",
+        Language::Persian => "بازی از نو شروع شد (بازیکنان باید دوباره بپیوندند).",
+    }
 }
 
-pub const fn reset_due_lack_of_players() -> &'static str {
-    "Everybody left :( Game is reset."
+pub const fn reset_due_lack_of_players(language: Language) -> &'static str {
+    match language {
+        Language::English => "Everybody left :( Game is reset.",
+        Language::Persian => "همه رفتند :( بازی از نو شروع شد.",
+    }
+}
+
+pub fn idle_notice(language: Language, minutes: u64) -> String {
+    match language {
+        Language::English => format!(
+            "💤 This game has been idle for a while. It will be reset in {} minutes unless someone plays.",
+            minutes
+        ),
+        Language::Persian => format!(
+            "💤 این بازی مدتی است بی‌حرکت مانده. اگر کسی بازی نکند تا {} دقیقه دیگر از نو شروع می‌شود.",
+            minutes
+        ),
+    }
+}
+
+pub const fn idle_reset(language: Language) -> &'static str {
+    match language {
+        Language::English => "💤 Nobody played for too long, so the game is reset.",
+        Language::Persian => "💤 مدت زیادی کسی بازی نکرد، پس بازی از نو شروع شد.",
+    }
 }
 
 pub const fn reset_hint() -> &'static str {
     "The game is reset."
 }
+
+pub const fn settings_title(language: Language) -> &'static str {
+    match language {
+        Language::English => "Settings (applied to the next game):",
+        Language::Persian => "تنظیمات (برای بازی بعدی اعمال می‌شود):",
+    }
+}
+
+pub const fn settings_back(language: Language) -> &'static str {
+    match language {
+        Language::English => "« Back",
+        Language::Persian => "« بازگشت",
+    }
+}
+
+pub const fn setting_title(language: Language, setting: Setting) -> &'static str {
+    match (language, setting) {
+        (Language::English, Setting::TargetScore) => "Target score",
+        (Language::English, Setting::Variant) => "Variant",
+        (Language::English, Setting::TurnTimeout) => "Turn timeout",
+        (Language::English, Setting::Verbosity) => "Verbosity",
+        (Language::English, Setting::Language) => "Language",
+        (Language::English, Setting::AiCommentary) => "AI commentary",
+        (Language::English, Setting::LobbyPolicy) => "Join after start",
+        (Language::English, Setting::SeasonMode) => "Seasons",
+        (Language::Persian, Setting::TargetScore) => "امتیاز هدف",
+        (Language::Persian, Setting::Variant) => "نوع بازی",
+        (Language::Persian, Setting::TurnTimeout) => "مهلت هر نوبت",
+        (Language::Persian, Setting::Verbosity) => "میزان پیام‌ها",
+        (Language::Persian, Setting::Language) => "زبان",
+        (Language::Persian, Setting::AiCommentary) => "گزارش هوش مصنوعی",
+        (Language::Persian, Setting::LobbyPolicy) => "پیوستن بعد از شروع",
+        (Language::Persian, Setting::SeasonMode) => "فصل‌ها",
+    }
+}
+
+pub const fn variant_label(language: Language, variant: Variant) -> &'static str {
+    match (language, variant) {
+        (Language::English, Variant::Classic) => "Classic",
+        (Language::English, Variant::DoubleSix) => "Double six",
+        (Language::Persian, Variant::Classic) => "کلاسیک",
+        (Language::Persian, Variant::DoubleSix) => "جفت شش",
+    }
+}
+
+pub fn turn_timeout_label(language: Language, turn_timeout: Option<u64>) -> String {
+    match (language, turn_timeout) {
+        (Language::English, Some(seconds)) => format!("{} min", seconds / 60),
+        (Language::English, None) => "Off".to_string(),
+        (Language::Persian, Some(seconds)) => format!("{} دقیقه", seconds / 60),
+        (Language::Persian, None) => "خاموش".to_string(),
+    }
+}
+
+pub const fn verbosity_label(language: Language, verbosity: Verbosity) -> &'static str {
+    match (language, verbosity) {
+        (Language::English, Verbosity::Quiet) => "Quiet",
+        (Language::English, Verbosity::Normal) => "Normal",
+        (Language::English, Verbosity::Verbose) => "Verbose",
+        (Language::Persian, Verbosity::Quiet) => "کم",
+        (Language::Persian, Verbosity::Normal) => "معمولی",
+        (Language::Persian, Verbosity::Verbose) => "زیاد",
+    }
+}
+
+// Each language is shown by its own name, whatever the chat language is.
+pub const fn language_label(language: Language) -> &'static str {
+    match language {
+        Language::English => "English",
+        Language::Persian => "فارسی",
+    }
+}
+
+pub const fn ai_commentary_label(language: Language, ai_commentary: bool) -> &'static str {
+    match (language, ai_commentary) {
+        (Language::English, true) => "On",
+        (Language::English, false) => "Off",
+        (Language::Persian, true) => "روشن",
+        (Language::Persian, false) => "خاموش",
+    }
+}
+
+pub const fn lobby_policy_label(language: Language, lobby_policy: LobbyPolicy) -> &'static str {
+    match (language, lobby_policy) {
+        (Language::English, LobbyPolicy::Open) => "Open",
+        (Language::English, LobbyPolicy::Closed) => "Closed",
+        (Language::Persian, LobbyPolicy::Open) => "آزاد",
+        (Language::Persian, LobbyPolicy::Closed) => "بسته",
+    }
+}
+
+pub const fn season_mode_label(language: Language, season_mode: SeasonMode) -> &'static str {
+    match (language, season_mode) {
+        (Language::English, SeasonMode::Off) => "Off",
+        (Language::English, SeasonMode::Monthly) => "Monthly",
+        (Language::English, SeasonMode::Manual) => "Started by admins",
+        (Language::Persian, SeasonMode::Off) => "خاموش",
+        (Language::Persian, SeasonMode::Monthly) => "ماهانه",
+        (Language::Persian, SeasonMode::Manual) => "با شروع مدیرها",
+    }
+}
+
+pub const fn substitute_usage(language: Language) -> &'static str {
+    match language {
+        Language::English => {
+            "Mention who takes your seat, e.g. /sub @username (or reply to their message)."
+        }
+        Language::Persian => {
+            "کسی را که جای شما را می‌گیرد منشن کنید، مثلاً /sub @username (یا به پیامش ریپلای کنید)."
+        }
+    }
+}
+
+pub const fn substitute_not_allowed(language: Language) -> &'static str {
+    match language {
+        Language::English => "Only admins can hand over someone else's seat :(",
+        Language::Persian => "فقط ادمین‌ها می‌توانند جای بازیکن دیگری را واگذار کنند :(",
+    }
+}
+
+pub const fn seat_not_found(language: Language) -> &'static str {
+    match language {
+        Language::English => "That player is not in the game :(",
+        Language::Persian => "این بازیکن در بازی نیست :(",
+    }
+}
+
+pub fn substitute_offer(
+    language: Language,
+    substitute: &String,
+    player_name: &String,
+    score: u16,
+) -> String {
+    match language {
+        Language::English => format!(
+            "{}, you are asked to take over {}'s seat with {} points.",
+            substitute, player_name, score
+        ),
+        Language::Persian => format!(
+            "{}، از شما خواسته شده جای {} را با {} امتیاز بگیرید.",
+            substitute, player_name, score
+        ),
+    }
+}
+
+pub fn substitute_offer_hint(substitute: &String, player_name: &String, score: u16) -> String {
+    format!(
+        "\
+        {} has to leave the game, so {} is asked to take over \
+        the seat and continue with {} points. \
+        Ask them to press the button to accept.",
+        player_name, substitute, score
+    )
+}
+
+pub const fn take_seat(language: Language) -> &'static str {
+    match language {
+        Language::English => "Take the seat",
+        Language::Persian => "گرفتن جا",
+    }
+}
+
+pub fn substituted(
+    language: Language,
+    substitute: &String,
+    player_name: &String,
+    score: u16,
+) -> String {
+    match language {
+        Language::English => format!(
+            "{} took over {}'s seat with {} points.",
+            substitute, player_name, score
+        ),
+        Language::Persian => format!(
+            "{} جای {} را با {} امتیاز گرفت.",
+            substitute, player_name, score
+        ),
+    }
+}
+
+pub fn substituted_hint(substitute: &String, player_name: &String, score: u16) -> String {
+    format!(
+        "\
+        {} left the game and {} took over the seat, \
+        keeping the {} points and the turn order. \
+        Welcome the new player.",
+        player_name, substitute, score
+    )
+}
+
+pub fn no_stats(language: Language, name: &String) -> String {
+    match language {
+        Language::English => format!("{} has not finished any game yet.", name),
+        Language::Persian => format!("{} هنوز هیچ بازی‌ای را تمام نکرده است.", name),
+    }
+}
+
+pub fn user_stats(language: Language, name: &String, stats: &UserStats) -> String {
+    match language {
+        Language::English => format!(
+            "Stats of {}:\n\
+            - Games played: {}\n\
+            - Wins: {}\n\
+            - Total rolls: {}\n\
+            - Ones rolled: {}\n\
+            - Busted turn points: {}\n\
+            - Largest single turn: {}\n\
+            - Average hold: {:.1}\n\
+            - Longest win streak: {}",
+            name,
+            stats.games_played,
+            stats.wins,
+            stats.total_rolls,
+            stats.ones_rolled,
+            stats.busted_points,
+            stats.largest_turn,
+            stats.average_hold(),
+            stats.longest_streak,
+        ),
+        Language::Persian => format!(
+            "آمار {}:\n\
+            - بازی‌ها: {}\n\
+            - بردها: {}\n\
+            - کل پرتاب‌ها: {}\n\
+            - تعداد یک‌ها: {}\n\
+            - امتیازهای سوخته: {}\n\
+            - بیشترین امتیاز یک نوبت: {}\n\
+            - میانگین نگه‌داشتن: {:.1}\n\
+            - طولانی‌ترین برد پیاپی: {}",
+            name,
+            stats.games_played,
+            stats.wins,
+            stats.total_rolls,
+            stats.ones_rolled,
+            stats.busted_points,
+            stats.largest_turn,
+            stats.average_hold(),
+            stats.longest_streak,
+        ),
+    }
+}
+
+pub fn board_title(language: Language, board: Board) -> String {
+    match (language, board) {
+        (Language::English, Board::Wins) => "🏆 Wins",
+        (Language::English, Board::WinRate) => "📈 Win rate",
+        (Language::English, Board::Rating) => "⭐ Rating",
+        (Language::Persian, Board::Wins) => "🏆 بردها",
+        (Language::Persian, Board::WinRate) => "📈 درصد برد",
+        (Language::Persian, Board::Rating) => "⭐ ریتینگ",
+    }
+    .to_string()
+}
+
+pub fn period_title(language: Language, period: Period) -> String {
+    match (language, period) {
+        (Language::English, Period::Week) => "This week",
+        (Language::English, Period::Month) => "This month",
+        (Language::English, Period::AllTime) => "All time",
+        (Language::Persian, Period::Week) => "این هفته",
+        (Language::Persian, Period::Month) => "این ماه",
+        (Language::Persian, Period::AllTime) => "همه زمان‌ها",
+        (Language::English, Period::Season(number)) => return format!("Season {}", number),
+        (Language::Persian, Period::Season(number)) => return format!("فصل {}", number),
+    }
+    .to_string()
+}
+
+pub fn wins_entry(language: Language, name: &String, wins: u32, games: u32) -> String {
+    match language {
+        Language::English => format!("{}: {} wins ({} games)", name, wins, games),
+        Language::Persian => format!("{}: {} برد ({} بازی)", name, wins, games),
+    }
+}
+
+pub fn win_rate_entry(
+    language: Language,
+    name: &String,
+    win_rate: f64,
+    wins: u32,
+    games: u32,
+) -> String {
+    match language {
+        Language::English => format!("{}: {:.0}% ({}/{})", name, win_rate, wins, games),
+        Language::Persian => format!("{}: {:.0}٪ ({}/{})", name, win_rate, wins, games),
+    }
+}
+
+pub fn rating_entry(language: Language, name: &String, rating: f64, games: u32) -> String {
+    match language {
+        Language::English => format!("{}: {:.0} ({} games)", name, rating, games),
+        Language::Persian => format!("{}: {:.0} ({} بازی)", name, rating, games),
+    }
+}
+
+pub fn user_rating(language: Language, chat_rating: Option<f64>, global_rating: f64) -> String {
+    match (language, chat_rating) {
+        (Language::English, Some(chat_rating)) => format!(
+            "- Rating: {:.0} in this group, {:.0} overall",
+            chat_rating, global_rating
+        ),
+        (Language::English, None) => format!("- Rating: {:.0}", global_rating),
+        (Language::Persian, Some(chat_rating)) => format!(
+            "- ریتینگ: {:.0} در این گروه، {:.0} در کل",
+            chat_rating, global_rating
+        ),
+        (Language::Persian, None) => format!("- ریتینگ: {:.0}", global_rating),
+    }
+}
+
+pub const fn no_ranked_players(language: Language) -> &'static str {
+    match language {
+        Language::English => "Nobody is ranked in this period yet.",
+        Language::Persian => "هنوز کسی در این بازه رتبه ندارد.",
+    }
+}
+
+pub const fn previous_page(language: Language) -> &'static str {
+    match language {
+        Language::English => "« Previous",
+        Language::Persian => "« قبلی",
+    }
+}
+
+pub const fn next_page(language: Language) -> &'static str {
+    match language {
+        Language::English => "Next »",
+        Language::Persian => "بعدی »",
+    }
+}
+
+pub fn achievement_unlocked(
+    language: Language,
+    name: &String,
+    emoji: &String,
+    title: &String,
+    description: &String,
+) -> String {
+    match language {
+        Language::English => format!("🏅 {} unlocked {} {}: {}", name, emoji, title, description),
+        Language::Persian => format!(
+            "🏅 {} نشان {} {} را گرفت: {}",
+            name, emoji, title, description
+        ),
+    }
+}
+
+pub fn profile_title(language: Language, name: &String) -> String {
+    match language {
+        Language::English => format!("Profile of {}", name),
+        Language::Persian => format!("پروفایل {}", name),
+    }
+}
+
+pub fn profile_summary(language: Language, games: u32, wins: u32, rating: f64) -> String {
+    match language {
+        Language::English => format!("Games: {}, wins: {}, rating: {:.0}", games, wins, rating),
+        Language::Persian => format!("بازی‌ها: {}، بردها: {}، ریتینگ: {:.0}", games, wins, rating),
+    }
+}
+
+pub const fn badges_title(language: Language) -> &'static str {
+    match language {
+        Language::English => "Badges:",
+        Language::Persian => "نشان‌ها:",
+    }
+}
+
+pub const fn no_badges(language: Language) -> &'static str {
+    match language {
+        Language::English => "No badges yet.",
+        Language::Persian => "هنوز نشانی ندارد.",
+    }
+}
+
+pub const fn luck_current_game_title(language: Language) -> &'static str {
+    match language {
+        Language::English => "🎲 Luck in the current game",
+        Language::Persian => "🎲 شانس در بازی فعلی",
+    }
+}
+
+pub fn luck_lifetime_title(language: Language, name: &String) -> String {
+    match language {
+        Language::English => format!("🎲 Lifetime luck of {}", name),
+        Language::Persian => format!("🎲 شانس همیشگی {}", name),
+    }
+}
+
+pub fn luck_no_rolls(language: Language, name: &String) -> String {
+    match language {
+        Language::English => format!("{} has not rolled any dice yet.", name),
+        Language::Persian => format!("{} هنوز تاسی نینداخته است.", name),
+    }
+}
+
+pub fn luck_summary(
+    language: Language,
+    name: &String,
+    rolls: u32,
+    ones_rate: f64,
+    chi_square: f64,
+    luck_index: f64,
+) -> String {
+    match language {
+        Language::English => format!(
+            "{} ({} rolls)\nOnes: {:.1}% (expected 16.7%)\nChi-square: {:.2}, luck index: {:+.2}",
+            name, rolls, ones_rate, chi_square, luck_index
+        ),
+        Language::Persian => format!(
+            "{} ({} پرتاب)\nیک‌ها: {:.1}٪ (مورد انتظار ۱۶.۷٪)\nکای‌دو: {:.2}، شاخص شانس: {:+.2}",
+            name, rolls, ones_rate, chi_square, luck_index
+        ),
+    }
+}
+
+pub fn luck_verdict(language: Language, luck_index: f64, is_biased: bool) -> &'static str {
+    match language {
+        Language::English => {
+            if is_biased {
+                "These dice look suspicious 🤨"
+            } else if luck_index >= 1.0 {
+                "The dice love you 🍀"
+            } else if luck_index <= -1.0 {
+                "The dice hate you 😿"
+            } else {
+                "Perfectly ordinary dice 😐"
+            }
+        }
+        Language::Persian => {
+            if is_biased {
+                "این تاس‌ها مشکوک به نظر می‌رسند 🤨"
+            } else if luck_index >= 1.0 {
+                "تاس‌ها عاشقت هستند 🍀"
+            } else if luck_index <= -1.0 {
+                "تاس‌ها از تو متنفرند 😿"
+            } else {
+                "تاس‌هایی کاملاً معمولی 😐"
+            }
+        }
+    }
+}
+
+pub const fn season_not_found(language: Language) -> &'static str {
+    match language {
+        Language::English => "There is no such season.",
+        Language::Persian => "چنین فصلی وجود ندارد.",
+    }
+}
+
+pub const fn seasons_disabled(language: Language) -> &'static str {
+    match language {
+        Language::English => "Seasons are off in this chat. Turn them on in /settings.",
+        Language::Persian => "فصل‌ها در این گروه خاموش هستند. از /settings روشنشان کنید.",
+    }
+}
+
+pub const fn settings_admin_only(language: Language) -> &'static str {
+    match language {
+        Language::English => "Only chat admins can change the settings.",
+        Language::Persian => "فقط مدیران گروه می‌توانند تنظیمات را تغییر دهند.",
+    }
+}
+
+pub const fn season_admin_only(language: Language) -> &'static str {
+    match language {
+        Language::English => "Only chat admins can start a new season.",
+        Language::Persian => "فقط مدیران گروه می‌توانند فصل جدیدی شروع کنند.",
+    }
+}
+
+pub fn season_info(language: Language, number: u32, started_at: &String) -> String {
+    match language {
+        Language::English => format!(
+            "Season {} is running since {}.\nSee the standings with /top season",
+            number, started_at
+        ),
+        Language::Persian => format!(
+            "فصل {} از {} در جریان است.\nجدول را با /top season ببینید",
+            number, started_at
+        ),
+    }
+}
+
+pub const fn no_season(language: Language) -> &'static str {
+    match language {
+        Language::English => "No season has started yet. It begins with the next finished game.",
+        Language::Persian => "هنوز فصلی شروع نشده است. با پایان بازی بعدی شروع می‌شود.",
+    }
+}
+
+pub fn season_over(language: Language, number: u32) -> String {
+    match language {
+        Language::English => format!("🏁 Season {} is over! Final standings:", number),
+        Language::Persian => format!("🏁 فصل {} تمام شد! جدول نهایی:", number),
+    }
+}
+
+pub const fn season_without_winners(language: Language) -> &'static str {
+    match language {
+        Language::English => "Nobody won a game this season.",
+        Language::Persian => "در این فصل کسی بازی‌ای نبرد.",
+    }
+}
+
+pub fn season_started(language: Language, number: u32) -> String {
+    match language {
+        Language::English => format!("🌱 Season {} starts now. Good luck!", number),
+        Language::Persian => format!("🌱 فصل {} همین حالا شروع شد. موفق باشید!", number),
+    }
+}
+
+pub const fn decisions_title(language: Language) -> &'static str {
+    match language {
+        Language::English => "🧠 Decision report (compared to optimal play)",
+        Language::Persian => "🧠 گزارش تصمیم‌ها (در مقایسه با بازی بهینه)",
+    }
+}
+
+pub fn decision_entry(
+    language: Language,
+    name: &String,
+    optimal_rate: f64,
+    win_chance_lost: f64,
+) -> String {
+    match language {
+        Language::English => format!(
+            "{}: {:.0}% optimal decisions, {:.1}% win chance lost",
+            name, optimal_rate, win_chance_lost
+        ),
+        Language::Persian => format!(
+            "{}: {:.0}٪ تصمیم بهینه، {:.1}٪ شانس برد از دست رفته",
+            name, optimal_rate, win_chance_lost
+        ),
+    }
+}
+
+pub const fn risk_profile(language: Language, risk_profile: RiskProfile) -> &'static str {
+    match (language, risk_profile) {
+        (Language::English, RiskProfile::Optimal) => "  Flawless play 🎯",
+        (Language::English, RiskProfile::Balanced) => "  Mixed mistakes ⚖️",
+        (Language::English, RiskProfile::HoldsTooEarly) => "  Holds too early 🐢",
+        (Language::English, RiskProfile::TooGreedy) => "  Too greedy 🐷",
+        (Language::Persian, RiskProfile::Optimal) => "  بازی بی‌نقص 🎯",
+        (Language::Persian, RiskProfile::Balanced) => "  اشتباه‌های متنوع ⚖️",
+        (Language::Persian, RiskProfile::HoldsTooEarly) => "  زود نگه می‌دارد 🐢",
+        (Language::Persian, RiskProfile::TooGreedy) => "  بیش از حد حریص 🐷",
+    }
+}
+
+pub const fn command_description(language: Language, command: Command) -> &'static str {
+    match (language, command) {
+        (Language::English, Command::Join) => "Join the game",
+        (Language::English, Command::Play) => "Start the game",
+        (Language::English, Command::Hold) => "Keep your turn points and pass the dice",
+        (Language::English, Command::Leave) => "Leave the game",
+        (Language::English, Command::Result) => "Show the scoreboard",
+        (Language::English, Command::Reset) => "Reset the game",
+        (Language::English, Command::Sub) => "Substitute a player",
+        (Language::English, Command::Settings) => "Change the chat settings",
+        (Language::English, Command::Top) => "Show the leaderboard",
+        (Language::English, Command::Season) => "Show the current season",
+        (Language::English, Command::Stats) => "Show player statistics",
+        (Language::English, Command::Profile) => "Show a player profile",
+        (Language::English, Command::Luck) => "Check how lucky your rolls are",
+        (Language::Persian, Command::Join) => "پیوستن به بازی",
+        (Language::Persian, Command::Play) => "شروع بازی",
+        (Language::Persian, Command::Hold) => "نگه داشتن امتیاز این نوبت و دادن تاس به نفر بعد",
+        (Language::Persian, Command::Leave) => "ترک بازی",
+        (Language::Persian, Command::Result) => "نمایش جدول امتیازها",
+        (Language::Persian, Command::Reset) => "شروع دوباره بازی",
+        (Language::Persian, Command::Sub) => "جایگزین کردن یک بازیکن",
+        (Language::Persian, Command::Settings) => "تغییر تنظیمات گروه",
+        (Language::Persian, Command::Top) => "نمایش جدول برترین‌ها",
+        (Language::Persian, Command::Season) => "نمایش فصل جاری",
+        (Language::Persian, Command::Stats) => "نمایش آمار بازیکن",
+        (Language::Persian, Command::Profile) => "نمایش پروفایل بازیکن",
+        (Language::Persian, Command::Luck) => "بررسی شانس تاس‌های شما",
+    }
+}
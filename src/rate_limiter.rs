@@ -0,0 +1,204 @@
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use dashmap::DashMap;
+use tokio::time::Instant;
+
+use super::telegram_types;
+
+// Limits published by Telegram: about 30 messages per second overall, one
+// message per second in a private chat and 20 messages per minute in a group.
+const GLOBAL_LIMIT: (usize, Duration) = (30, Duration::from_secs(1));
+const PRIVATE_CHAT_LIMIT: (usize, Duration) = (1, Duration::from_secs(1));
+const GROUP_CHAT_LIMIT: (usize, Duration) = (20, Duration::from_secs(60));
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy)]
+pub enum Scope {
+    Global,
+    Chat(telegram_types::ChatId),
+}
+
+struct Window {
+    limit: usize,
+    period: Duration,
+    sent: VecDeque<Instant>,
+}
+
+impl Window {
+    fn new((limit, period): (usize, Duration)) -> Window {
+        Window {
+            limit,
+            period,
+            sent: VecDeque::new(),
+        }
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while self
+            .sent
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= self.period)
+        {
+            self.sent.pop_front();
+        }
+    }
+
+    fn wait_time(&mut self, now: Instant) -> Option<Duration> {
+        self.expire(now);
+        if self.sent.len() < self.limit {
+            return None;
+        }
+        self.sent
+            .front()
+            .map(|oldest| (*oldest + self.period).duration_since(now))
+    }
+}
+
+pub struct RateLimiter {
+    global: Mutex<Window>,
+    chats: DashMap<telegram_types::ChatId, Window>,
+    last_pruned: Mutex<Instant>,
+    private_chat_limit: (usize, Duration),
+    group_chat_limit: (usize, Duration),
+}
+
+impl RateLimiter {
+    fn with_limits(
+        global_limit: (usize, Duration),
+        private_chat_limit: (usize, Duration),
+        group_chat_limit: (usize, Duration),
+    ) -> RateLimiter {
+        RateLimiter {
+            global: Mutex::new(Window::new(global_limit)),
+            chats: DashMap::new(),
+            last_pruned: Mutex::new(Instant::now()),
+            private_chat_limit,
+            group_chat_limit,
+        }
+    }
+
+    pub fn new() -> RateLimiter {
+        RateLimiter::with_limits(GLOBAL_LIMIT, PRIVATE_CHAT_LIMIT, GROUP_CHAT_LIMIT)
+    }
+
+    #[cfg(test)]
+    pub fn unlimited() -> RateLimiter {
+        let unlimited = (usize::MAX, Duration::ZERO);
+        RateLimiter::with_limits(unlimited, unlimited, unlimited)
+    }
+
+    // A window whose sends have all expired is the same as a new one, so such
+    // windows are dropped once in a while instead of piling up for every chat
+    // the bot has ever written to.
+    fn prune(&self, now: Instant) {
+        {
+            let mut last_pruned = self
+                .last_pruned
+                .lock()
+                .unwrap_or_else(|error| error.into_inner());
+            if now.duration_since(*last_pruned) < PRUNE_INTERVAL {
+                return;
+            }
+            *last_pruned = now;
+        }
+        self.chats.retain(|_, window| {
+            window.expire(now);
+            !window.sent.is_empty()
+        });
+    }
+
+    // Waits until the message fits both the global and the chat window, then
+    // reserves a slot in both. The chat entry is locked before the global
+    // window, always in this order.
+    pub async fn acquire(&self, scope: Scope) {
+        loop {
+            let now = Instant::now();
+            self.prune(now);
+            let wait = {
+                let mut chat = match scope {
+                    Scope::Chat(chat_id) => Some(self.chats.entry(chat_id).or_insert_with(|| {
+                        Window::new(if chat_id.is_group() {
+                            self.group_chat_limit
+                        } else {
+                            self.private_chat_limit
+                        })
+                    })),
+                    Scope::Global => None,
+                };
+                let mut global = self
+                    .global
+                    .lock()
+                    .unwrap_or_else(|error| error.into_inner());
+                match chat
+                    .as_mut()
+                    .and_then(|chat| chat.wait_time(now))
+                    .or_else(|| global.wait_time(now))
+                {
+                    Some(wait) => wait,
+                    None => {
+                        global.sent.push_back(now);
+                        if let Some(chat) = chat.as_mut() {
+                            chat.sent.push_back(now);
+                        }
+                        return;
+                    }
+                }
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNLIMITED: (usize, Duration) = (usize::MAX, Duration::ZERO);
+
+    fn groups() -> (telegram_types::ChatId, telegram_types::ChatId) {
+        (
+            telegram_types::ChatId::from_i64(-1),
+            telegram_types::ChatId::from_i64(-2),
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn group_messages_wait_for_the_chat_window() {
+        let limiter = RateLimiter::with_limits(UNLIMITED, UNLIMITED, (2, Duration::from_secs(60)));
+        let (group, other_group) = groups();
+        let started = Instant::now();
+        limiter.acquire(Scope::Chat(group)).await;
+        limiter.acquire(Scope::Chat(group)).await;
+        // Another chat has its own window.
+        limiter.acquire(Scope::Chat(other_group)).await;
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        limiter.acquire(Scope::Chat(group)).await;
+        assert_eq!(started.elapsed(), Duration::from_secs(60));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn every_message_waits_for_the_global_window() {
+        let limiter = RateLimiter::with_limits((2, Duration::from_secs(1)), UNLIMITED, UNLIMITED);
+        let (group, other_group) = groups();
+        let started = Instant::now();
+        limiter.acquire(Scope::Chat(group)).await;
+        limiter.acquire(Scope::Global).await;
+        limiter.acquire(Scope::Chat(other_group)).await;
+        assert_eq!(started.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_chat_windows_are_pruned() {
+        let limiter =
+            RateLimiter::with_limits(UNLIMITED, UNLIMITED, (20, Duration::from_secs(120)));
+        let (group, other_group) = groups();
+        limiter.acquire(Scope::Chat(group)).await;
+        tokio::time::advance(Duration::from_secs(70)).await;
+        limiter.acquire(Scope::Chat(other_group)).await;
+        assert_eq!(limiter.chats.len(), 2);
+        tokio::time::advance(PRUNE_INTERVAL).await;
+        limiter.acquire(Scope::Global).await;
+        assert!(!limiter.chats.contains_key(&group));
+        assert!(limiter.chats.contains_key(&other_group));
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::game_model;
+use super::storage;
+use super::telegram_types;
+
+const INITIAL_RATING: f64 = 1500.0;
+const K_FACTOR: f64 = 32.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Rating {
+    pub value: f64,
+    pub games: u32,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Rating {
+            value: INITIAL_RATING,
+            games: 0,
+        }
+    }
+}
+
+pub type ChatRatings = HashMap<telegram_types::UserId, Rating>;
+
+pub fn get_chat_rating(
+    chat_ratings: Option<&ChatRatings>,
+    user_id: telegram_types::UserId,
+) -> Rating {
+    chat_ratings
+        .and_then(|ratings| ratings.get(&user_id).copied())
+        .unwrap_or_default()
+}
+
+// A player rated in both chats keeps the rating that is based on more games.
+pub fn merge(chat_ratings: &mut ChatRatings, other: ChatRatings) {
+    for (user_id, rating) in other {
+        let existing = chat_ratings.entry(user_id).or_insert(rating);
+        if rating.games > existing.games {
+            *existing = rating;
+        }
+    }
+}
+
+fn get_places(finished_game: &game_model::FinishedGame) -> Vec<usize> {
+    finished_game
+        .players
+        .iter()
+        .map(|player| {
+            if player.user_id == finished_game.winner {
+                0
+            } else {
+                1 + finished_game
+                    .players
+                    .iter()
+                    .filter(|other| {
+                        other.user_id != finished_game.winner && other.score > player.score
+                    })
+                    .count()
+            }
+        })
+        .collect()
+}
+
+// Multiplayer Elo: each pair of players is scored as one match decided by their
+// final places and the rating change is averaged over the opponents.
+fn rate(ratings: &[Rating], places: &[usize]) -> Vec<Rating> {
+    let opponents = (ratings.len() - 1).max(1) as f64;
+    ratings
+        .iter()
+        .zip(places)
+        .enumerate()
+        .map(|(i, (rating, place))| {
+            let change: f64 = ratings
+                .iter()
+                .zip(places)
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, (other, other_place))| {
+                    let expected = 1.0 / (1.0 + 10f64.powf((other.value - rating.value) / 400.0));
+                    let actual = match place.cmp(other_place) {
+                        std::cmp::Ordering::Less => 1.0,
+                        std::cmp::Ordering::Equal => 0.5,
+                        std::cmp::Ordering::Greater => 0.0,
+                    };
+                    actual - expected
+                })
+                .sum();
+            Rating {
+                value: rating.value + K_FACTOR * change / opponents,
+                games: rating.games + 1,
+            }
+        })
+        .collect()
+}
+
+pub fn record(
+    transaction: &mut storage::Transaction,
+    chat_id: telegram_types::ChatId,
+    finished_game: &game_model::FinishedGame,
+) {
+    let places = get_places(finished_game);
+
+    let global: Vec<Rating> = finished_game
+        .players
+        .iter()
+        .map(|player| transaction.rating(player.user_id))
+        .collect();
+    for (player, rating) in finished_game.players.iter().zip(rate(&global, &places)) {
+        transaction.set_rating(player.user_id, rating);
+    }
+
+    update(transaction.chat_ratings_mut(chat_id), finished_game);
+}
+
+pub fn update(chat_ratings: &mut ChatRatings, finished_game: &game_model::FinishedGame) {
+    let places = get_places(finished_game);
+    let chat: Vec<Rating> = finished_game
+        .players
+        .iter()
+        .map(|player| {
+            chat_ratings
+                .get(&player.user_id)
+                .copied()
+                .unwrap_or_default()
+        })
+        .collect();
+    for (player, rating) in finished_game.players.iter().zip(rate(&chat, &places)) {
+        chat_ratings.insert(player.user_id, rating);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finished_game(scores: &[u16]) -> game_model::FinishedGame {
+        let players: Vec<game_model::FinishedPlayer> = scores
+            .iter()
+            .enumerate()
+            .map(|(index, score)| game_model::FinishedPlayer {
+                user_id: serde_json::from_value(serde_json::json!(index)).unwrap(),
+                name: format!("Player {}", index),
+                score: *score,
+                tally: game_model::PlayerTally::default(),
+            })
+            .collect();
+        game_model::FinishedGame {
+            winner: players[0].user_id,
+            players,
+        }
+    }
+
+    fn rate_equals(places: &[usize]) -> Vec<f64> {
+        rate(&vec![Rating::default(); places.len()], places)
+            .iter()
+            .map(|rating| rating.value - INITIAL_RATING)
+            .collect()
+    }
+
+    #[test]
+    fn second_place_gains_against_fourth() {
+        let changes = rate_equals(&[0, 1, 2, 3]);
+        // Lost to the winner, beat the other two: (-0.5 + 0.5 + 0.5) / 3 games.
+        assert!((changes[1] - K_FACTOR * 0.5 / 3.0).abs() < 1e-9);
+        assert!(changes[0] > changes[1]);
+        assert!(changes[1] > 0.0);
+        assert!(changes[2] < 0.0);
+        assert!(changes[3] < changes[2]);
+    }
+
+    #[test]
+    fn equal_ratings_change_by_zero_in_total() {
+        for places in [vec![0, 1], vec![0, 1, 2], vec![0, 1, 1, 3]] {
+            assert!(rate_equals(&places).iter().sum::<f64>().abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn tied_players_share_their_place() {
+        let finished_game = finished_game(&[100, 40, 40, 10]);
+        let places = get_places(&finished_game);
+        assert_eq!(places, vec![0, 1, 1, 3]);
+        // The tie counts as half a win for both, so they end up level.
+        let changes = rate_equals(&places);
+        assert!((changes[1] - changes[2]).abs() < 1e-9);
+        assert!((changes[1] - K_FACTOR * (-0.5 + 0.0 + 0.5) / 3.0).abs() < 1e-9);
+    }
+}
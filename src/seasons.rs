@@ -0,0 +1,299 @@
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+
+use super::game_model;
+use super::leaderboard;
+use super::message_action;
+use super::ratings;
+use super::storage;
+use super::telegram_types;
+use crate::prompt_messages::{
+    no_season, season_admin_only, season_info, season_over, season_started, season_without_winners,
+    seasons_disabled, wins_entry,
+};
+use crate::settings::{ChatSettings, Language, SeasonMode};
+
+const MEDALS: [&str; 3] = ["🥇", "🥈", "🥉"];
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Season {
+    pub number: u32,
+    pub started_at: u64,
+    pub ended_at: Option<u64>,
+    pub ratings: ratings::ChatRatings,
+}
+
+impl Season {
+    fn new(number: u32, started_at: u64) -> Season {
+        Season {
+            number,
+            started_at,
+            ended_at: None,
+            ratings: ratings::ChatRatings::new(),
+        }
+    }
+
+    pub fn range(&self) -> (u64, u64) {
+        (self.started_at, self.ended_at.unwrap_or(u64::MAX))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ChatSeasons {
+    pub current: Option<Season>,
+    pub archive: Vec<Season>,
+}
+
+impl ChatSeasons {
+    pub fn current_number(&self) -> Option<u32> {
+        self.current.as_ref().map(|season| season.number)
+    }
+
+    pub fn get(&self, number: u32) -> Option<&Season> {
+        self.current
+            .iter()
+            .chain(self.archive.iter())
+            .find(|season| season.number == number)
+    }
+
+    fn get_current_mut(&mut self, now: u64) -> &mut Season {
+        let number = self.archive.last().map_or(0, |season| season.number) + 1;
+        self.current.get_or_insert_with(|| Season::new(number, now))
+    }
+
+    // Keeps this chat's current season and archives the other chat's seasons
+    // next to its own, oldest first.
+    pub fn merge(&mut self, other: ChatSeasons) {
+        let mut seasons = other.archive;
+        match (&self.current, other.current) {
+            (None, current) => self.current = current,
+            (Some(current), Some(mut season)) => {
+                season.ended_at = Some(current.started_at.max(season.started_at));
+                seasons.push(season);
+            }
+            (Some(_), None) => (),
+        }
+        self.archive.append(&mut seasons);
+        self.archive.sort_by_key(|season| season.started_at);
+    }
+
+    fn end_current(&mut self, now: u64) -> Option<&Season> {
+        let mut season = self.current.take()?;
+        season.ended_at = Some(now);
+        self.archive.push(season);
+        self.archive.last()
+    }
+}
+
+fn standings(language: Language, season: &Season, records: &[leaderboard::GameRecord]) -> String {
+    let entries = leaderboard::get_entries(
+        records,
+        Some(&season.ratings),
+        leaderboard::Board::Wins,
+        season.range(),
+    );
+    let podium = entries
+        .iter()
+        .zip(MEDALS)
+        .fold("".to_string(), |res, (entry, medal)| {
+            format!(
+                "{}\n{} {}",
+                res,
+                medal,
+                wins_entry(language, &entry.name, entry.wins, entry.games)
+            )
+        });
+    if podium.is_empty() {
+        format!(
+            "{}\n{}",
+            season_over(language, season.number),
+            season_without_winners(language)
+        )
+    } else {
+        format!("{}{}", season_over(language, season.number), podium)
+    }
+}
+
+fn announcement(text: String) -> message_action::MessageAction {
+    message_action::MessageAction::Send(message_action::MessageInfo {
+        text,
+        reply_to_message_id: None,
+        reply_markup: None,
+        hint: None,
+        is_premium: false,
+        parse_mode: None,
+    })
+}
+
+fn end_season(
+    seasons: &mut ChatSeasons,
+    records: &[leaderboard::GameRecord],
+    now: u64,
+    language: Language,
+) -> Option<String> {
+    let season = seasons.end_current(now)?;
+    Some(standings(language, season, records))
+}
+
+fn rollover(
+    seasons: &mut ChatSeasons,
+    records: &[leaderboard::GameRecord],
+    now: u64,
+    language: Language,
+) -> message_action::MessageAction {
+    let standings = end_season(seasons, records, now, language);
+    let started = season_started(language, seasons.get_current_mut(now).number);
+    announcement(match standings {
+        Some(standings) => format!("{}\n\n{}", standings, started),
+        None => started,
+    })
+}
+
+pub fn record(
+    transaction: &mut storage::Transaction,
+    chat_id: telegram_types::ChatId,
+    settings: &ChatSettings,
+    finished_game: &game_model::FinishedGame,
+) {
+    if settings.season_mode == SeasonMode::Off {
+        return;
+    }
+    let season = transaction
+        .seasons_mut(chat_id)
+        .get_current_mut(game_model::unix_now());
+    ratings::update(&mut season.ratings, finished_game);
+}
+
+pub fn check_rollover(
+    transaction: &mut storage::Transaction,
+    chat_id: telegram_types::ChatId,
+) -> Option<message_action::MessageAction> {
+    let settings = transaction.settings(chat_id);
+    if settings.season_mode != SeasonMode::Monthly {
+        return None;
+    }
+    let month_start = leaderboard::Period::Month.start();
+    if transaction.seasons(chat_id)?.current?.started_at >= month_start {
+        return None;
+    }
+    // The next season only starts with the next finished game, so a chat that
+    // stopped playing gets one final post instead of an empty season every month.
+    let records = transaction.records(chat_id);
+    end_season(
+        transaction.seasons_mut(chat_id),
+        &records,
+        month_start,
+        settings.language,
+    )
+    .map(announcement)
+}
+
+pub fn handle_command(
+    transaction: &mut storage::Transaction,
+    settings: &ChatSettings,
+    message: &telegram_types::Message,
+    args: &[String],
+    is_admin: bool,
+) -> message_action::MessageAction {
+    let language = settings.language;
+    let is_new_season = args.first().map(String::as_str) == Some("new");
+    let text = if settings.season_mode == SeasonMode::Off {
+        seasons_disabled(language).to_string()
+    } else if is_new_season && !is_admin {
+        season_admin_only(language).to_string()
+    } else if is_new_season {
+        let records = transaction.records(message.chat.id);
+        return rollover(
+            transaction.seasons_mut(message.chat.id),
+            &records,
+            game_model::unix_now(),
+            language,
+        );
+    } else {
+        match transaction
+            .seasons(message.chat.id)
+            .and_then(|seasons| seasons.current)
+            .map(|season| (season.number, season.started_at))
+        {
+            Some((number, started_at)) => season_info(
+                language,
+                number,
+                &DateTime::from_timestamp(started_at as i64, 0)
+                    .map(|date| date.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default(),
+            ),
+            None => no_season(language).to_string(),
+        }
+    };
+    message_action::MessageAction::Send(message_action::MessageInfo {
+        text,
+        reply_to_message_id: Some(message.message_id),
+        reply_markup: None,
+        hint: None,
+        is_premium: false,
+        parse_mode: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{InMemoryStorage, Storage};
+
+    fn finished_game() -> game_model::FinishedGame {
+        let players: Vec<game_model::FinishedPlayer> = [1, 2]
+            .iter()
+            .map(|user_id| game_model::FinishedPlayer {
+                user_id: serde_json::from_value(serde_json::json!(user_id)).unwrap(),
+                name: format!("Player {}", user_id),
+                score: if *user_id == 1 { 100 } else { 40 },
+                tally: game_model::PlayerTally::default(),
+            })
+            .collect();
+        game_model::FinishedGame {
+            winner: players[0].user_id,
+            players,
+        }
+    }
+
+    #[test]
+    fn monthly_season_ends_once_and_restarts_with_the_next_game() {
+        let storage = InMemoryStorage::default();
+        let chat_id = telegram_types::ChatId::from_i64(-1);
+        let month_start = leaderboard::Period::Month.start();
+        let mut posts = vec![];
+        storage.transaction(None, &mut |transaction| {
+            transaction.settings_mut(chat_id).season_mode = SeasonMode::Monthly;
+            transaction.seasons_mut(chat_id).current = Some(Season::new(1, month_start - 1));
+            // Nobody plays for three months.
+            for _ in 0..3 {
+                posts.extend(check_rollover(transaction, chat_id));
+            }
+        });
+        assert_eq!(posts.len(), 1);
+        storage.transaction(None, &mut |transaction| {
+            let seasons = transaction.seasons(chat_id).unwrap();
+            assert!(seasons.current.is_none());
+            assert_eq!(seasons.archive[0].ended_at, Some(month_start));
+
+            let settings = transaction.settings(chat_id);
+            record(transaction, chat_id, &settings, &finished_game());
+            let current = transaction.seasons(chat_id).unwrap().current.unwrap();
+            assert_eq!(current.number, 2);
+            assert!(current.started_at >= month_start);
+            assert!(check_rollover(transaction, chat_id).is_none());
+        });
+    }
+
+    #[test]
+    fn new_season_command_starts_the_next_season_right_away() {
+        let mut seasons = ChatSeasons {
+            current: Some(Season::new(1, 100)),
+            archive: vec![],
+        };
+        rollover(&mut seasons, &[], 200, Language::English);
+        assert_eq!(seasons.archive[0].range(), (100, 200));
+        let current = seasons.current.unwrap();
+        assert_eq!((current.number, current.started_at), (2, 200));
+    }
+}
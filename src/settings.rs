@@ -0,0 +1,313 @@
+use serde::{Deserialize, Serialize};
+
+use super::message_action;
+use super::telegram_types;
+use crate::prompt_messages::{
+    ai_commentary_label, language_label, lobby_policy_label, season_mode_label, setting_title,
+    settings_back, settings_title, turn_timeout_label, variant_label, verbosity_label,
+};
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum Variant {
+    Classic,
+    DoubleSix,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum Language {
+    English,
+    Persian,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum LobbyPolicy {
+    Open,
+    Closed,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum SeasonMode {
+    Off,
+    Monthly,
+    Manual,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct ChatSettings {
+    pub target_score: u16,
+    pub variant: Variant,
+    pub turn_timeout: Option<u64>,
+    pub verbosity: Verbosity,
+    pub language: Language,
+    pub ai_commentary: bool,
+    pub lobby_policy: LobbyPolicy,
+    pub season_mode: SeasonMode,
+}
+
+impl Default for ChatSettings {
+    fn default() -> Self {
+        ChatSettings {
+            target_score: 100,
+            variant: Variant::Classic,
+            turn_timeout: None,
+            verbosity: Verbosity::Normal,
+            language: Language::English,
+            ai_commentary: true,
+            lobby_policy: LobbyPolicy::Open,
+            season_mode: SeasonMode::Off,
+        }
+    }
+}
+
+const CALLBACK_PREFIX: &str = "settings";
+
+pub const TARGET_SCORES: [u16; 4] = [50, 100, 150, 200];
+const TURN_TIMEOUTS: [Option<u64>; 4] = [None, Some(60), Some(120), Some(300)];
+const VARIANTS: [Variant; 2] = [Variant::Classic, Variant::DoubleSix];
+const VERBOSITIES: [Verbosity; 3] = [Verbosity::Quiet, Verbosity::Normal, Verbosity::Verbose];
+const LANGUAGES: [Language; 2] = [Language::English, Language::Persian];
+const AI_COMMENTARY: [bool; 2] = [true, false];
+const LOBBY_POLICIES: [LobbyPolicy; 2] = [LobbyPolicy::Open, LobbyPolicy::Closed];
+const SEASON_MODES: [SeasonMode; 3] = [SeasonMode::Off, SeasonMode::Monthly, SeasonMode::Manual];
+
+#[derive(Clone, Copy)]
+pub enum Setting {
+    TargetScore,
+    Variant,
+    TurnTimeout,
+    Verbosity,
+    Language,
+    AiCommentary,
+    LobbyPolicy,
+    SeasonMode,
+}
+
+impl Setting {
+    const ALL: [Setting; 8] = [
+        Setting::TargetScore,
+        Setting::Variant,
+        Setting::TurnTimeout,
+        Setting::Verbosity,
+        Setting::Language,
+        Setting::AiCommentary,
+        Setting::LobbyPolicy,
+        Setting::SeasonMode,
+    ];
+
+    fn key(&self) -> &'static str {
+        match self {
+            Setting::TargetScore => "target",
+            Setting::Variant => "variant",
+            Setting::TurnTimeout => "timeout",
+            Setting::Verbosity => "verbosity",
+            Setting::Language => "language",
+            Setting::AiCommentary => "ai",
+            Setting::LobbyPolicy => "lobby",
+            Setting::SeasonMode => "season",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Setting> {
+        Setting::ALL
+            .into_iter()
+            .find(|setting| setting.key() == key)
+    }
+
+    fn current(&self, settings: &ChatSettings) -> String {
+        let language = settings.language;
+        match self {
+            Setting::TargetScore => settings.target_score.to_string(),
+            Setting::Variant => variant_label(language, settings.variant).to_string(),
+            Setting::TurnTimeout => turn_timeout_label(language, settings.turn_timeout),
+            Setting::Verbosity => verbosity_label(language, settings.verbosity).to_string(),
+            Setting::Language => language_label(settings.language).to_string(),
+            Setting::AiCommentary => {
+                ai_commentary_label(language, settings.ai_commentary).to_string()
+            }
+            Setting::LobbyPolicy => lobby_policy_label(language, settings.lobby_policy).to_string(),
+            Setting::SeasonMode => season_mode_label(language, settings.season_mode).to_string(),
+        }
+    }
+
+    fn choices(&self, language: Language) -> Vec<String> {
+        match self {
+            Setting::TargetScore => TARGET_SCORES.map(|value| value.to_string()).to_vec(),
+            Setting::Variant => VARIANTS
+                .map(|value| variant_label(language, value).to_string())
+                .to_vec(),
+            Setting::TurnTimeout => TURN_TIMEOUTS
+                .map(|value| turn_timeout_label(language, value))
+                .to_vec(),
+            Setting::Verbosity => VERBOSITIES
+                .map(|value| verbosity_label(language, value).to_string())
+                .to_vec(),
+            Setting::Language => LANGUAGES
+                .map(|value| language_label(value).to_string())
+                .to_vec(),
+            Setting::AiCommentary => AI_COMMENTARY
+                .map(|value| ai_commentary_label(language, value).to_string())
+                .to_vec(),
+            Setting::LobbyPolicy => LOBBY_POLICIES
+                .map(|value| lobby_policy_label(language, value).to_string())
+                .to_vec(),
+            Setting::SeasonMode => SEASON_MODES
+                .map(|value| season_mode_label(language, value).to_string())
+                .to_vec(),
+        }
+    }
+
+    fn apply(&self, settings: &mut ChatSettings, index: usize) -> bool {
+        match self {
+            Setting::TargetScore => TARGET_SCORES
+                .get(index)
+                .map(|value| settings.target_score = *value),
+            Setting::Variant => VARIANTS.get(index).map(|value| settings.variant = *value),
+            Setting::TurnTimeout => TURN_TIMEOUTS
+                .get(index)
+                .map(|value| settings.turn_timeout = *value),
+            Setting::Verbosity => VERBOSITIES
+                .get(index)
+                .map(|value| settings.verbosity = *value),
+            Setting::Language => LANGUAGES.get(index).map(|value| settings.language = *value),
+            Setting::AiCommentary => AI_COMMENTARY
+                .get(index)
+                .map(|value| settings.ai_commentary = *value),
+            Setting::LobbyPolicy => LOBBY_POLICIES
+                .get(index)
+                .map(|value| settings.lobby_policy = *value),
+            Setting::SeasonMode => SEASON_MODES
+                .get(index)
+                .map(|value| settings.season_mode = *value),
+        }
+        .is_some()
+    }
+}
+
+fn button(text: String, callback_data: String) -> telegram_types::InlineKeyboardButton {
+    telegram_types::InlineKeyboardButton {
+        text,
+        callback_data: Some(callback_data),
+    }
+}
+
+fn main_menu(settings: &ChatSettings) -> (String, telegram_types::ReplyMarkup) {
+    let keyboard = Setting::ALL
+        .iter()
+        .map(|setting| {
+            vec![button(
+                format!(
+                    "{}: {}",
+                    setting_title(settings.language, *setting),
+                    setting.current(settings)
+                ),
+                format!("{}:{}", CALLBACK_PREFIX, setting.key()),
+            )]
+        })
+        .collect();
+    (
+        settings_title(settings.language).to_string(),
+        telegram_types::ReplyMarkup {
+            inline_keyboard: Some(keyboard),
+        },
+    )
+}
+
+fn setting_menu(
+    settings: &ChatSettings,
+    setting: Setting,
+) -> (String, telegram_types::ReplyMarkup) {
+    let current = setting.current(settings);
+    let mut keyboard: Vec<Vec<telegram_types::InlineKeyboardButton>> = setting
+        .choices(settings.language)
+        .into_iter()
+        .enumerate()
+        .map(|(index, choice)| {
+            let text = if choice == current {
+                format!("✓ {}", choice)
+            } else {
+                choice
+            };
+            vec![button(
+                text,
+                format!("{}:{}:{}", CALLBACK_PREFIX, setting.key(), index),
+            )]
+        })
+        .collect();
+    keyboard.push(vec![button(
+        settings_back(settings.language).to_string(),
+        CALLBACK_PREFIX.to_string(),
+    )]);
+    (
+        setting_title(settings.language, setting).to_string(),
+        telegram_types::ReplyMarkup {
+            inline_keyboard: Some(keyboard),
+        },
+    )
+}
+
+pub fn is_settings_callback(data: &str) -> bool {
+    data == CALLBACK_PREFIX || data.starts_with(&format!("{}:", CALLBACK_PREFIX))
+}
+
+pub fn handle_command(
+    settings: &ChatSettings,
+    message: &telegram_types::Message,
+) -> message_action::MessageAction {
+    let (text, reply_markup) = main_menu(settings);
+    message_action::MessageAction::Send(message_action::MessageInfo {
+        text,
+        reply_to_message_id: Some(message.message_id),
+        reply_markup: Some(reply_markup),
+        hint: None,
+        is_premium: false,
+        parse_mode: None,
+    })
+}
+
+pub fn handle_callback_query(
+    settings: &mut ChatSettings,
+    message: &telegram_types::Message,
+    data: &str,
+) -> Vec<message_action::MessageAction> {
+    let mut parts = data.split(':').skip(1);
+    let menu = match (parts.next(), parts.next()) {
+        (None, _) => main_menu(settings),
+        (Some(key), None) => match Setting::from_key(key) {
+            Some(setting) => setting_menu(settings, setting),
+            None => return vec![],
+        },
+        (Some(key), Some(index)) => {
+            let Some(setting) = Setting::from_key(key) else {
+                return vec![];
+            };
+            let Ok(index) = index.parse::<usize>() else {
+                return vec![];
+            };
+            if !setting.apply(settings, index) {
+                return vec![];
+            }
+            main_menu(settings)
+        }
+    };
+    let (text, reply_markup) = menu;
+    vec![message_action::MessageAction::Edit(
+        message_action::EditMessageInfo {
+            message_id: message.message_id,
+            message_info: message_action::MessageInfo {
+                text,
+                reply_to_message_id: None,
+                reply_markup: Some(reply_markup),
+                hint: None,
+                is_premium: false,
+                parse_mode: None,
+            },
+        },
+    )]
+}
@@ -0,0 +1,81 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::storage::{Data, InMemoryStorage};
+use super::telegram_types;
+
+const DEFAULT_SNAPSHOT_FILE: &str = "games.json";
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Snapshot<T> {
+    // Updates whose changes are part of the data, oldest first.
+    pub applied_updates: VecDeque<telegram_types::UpdateId>,
+    // The last journal entry whose changes are part of the data.
+    pub sequence: u64,
+    pub data: T,
+}
+
+pub fn get_snapshot_path() -> PathBuf {
+    std::env::var("SNAPSHOT_FILE")
+        .unwrap_or(DEFAULT_SNAPSHOT_FILE.to_string())
+        .into()
+}
+
+pub fn load(path: &Path) -> Snapshot<Data> {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Snapshot::default(),
+        Err(err) => {
+            tracing::error!("Can not read snapshot {}, error: {}", path.display(), err);
+            return Snapshot::default();
+        }
+    };
+    match serde_json::from_slice::<Snapshot<Data>>(&content) {
+        Ok(snapshot) => snapshot,
+        Err(err) => {
+            tracing::error!("Can not parse snapshot {}, error: {}", path.display(), err);
+            Snapshot::default()
+        }
+    }
+}
+
+// The file is replaced atomically by renaming a temporary file.
+pub fn save(
+    path: &Path,
+    data: &Data,
+    applied_updates: &VecDeque<telegram_types::UpdateId>,
+    sequence: u64,
+) -> std::io::Result<()> {
+    let content = serde_json::to_vec(&Snapshot {
+        applied_updates: applied_updates.clone(),
+        sequence,
+        data,
+    })?;
+    let temporary_path = path.with_extension("tmp");
+    let mut file = fs::File::create(&temporary_path)?;
+    file.write_all(&content)?;
+    file.sync_all()?;
+    fs::rename(&temporary_path, path)
+}
+
+pub async fn run(storage: Arc<InMemoryStorage>) {
+    let mut interval = tokio::time::interval(SNAPSHOT_INTERVAL);
+    loop {
+        interval.tick().await;
+        let storage = storage.clone();
+        match tokio::task::spawn_blocking(move || storage.compact()).await {
+            Ok(Ok(())) => (),
+            Ok(Err(err)) => tracing::error!("Can not write snapshot, error: {}", err),
+            Err(err) => tracing::error!("Snapshot task failed, error: {}", err),
+        }
+    }
+}
@@ -0,0 +1,438 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    path::Path,
+    sync::{Mutex, MutexGuard},
+};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::game_model;
+use super::leaderboard;
+use super::ratings;
+use super::seasons;
+use super::settings;
+use super::stats;
+use super::storage::{self, Changes, Source, Storage, MAX_APPLIED_UPDATES};
+use super::telegram_types;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS games (
+        chat_id INTEGER PRIMARY KEY,
+        state TEXT NOT NULL,
+        deadline INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS games_by_deadline ON games (deadline);
+    CREATE TABLE IF NOT EXISTS settings (chat_id INTEGER PRIMARY KEY, settings TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS game_records (
+        chat_id INTEGER NOT NULL,
+        finished_at INTEGER NOT NULL,
+        record TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS game_records_by_chat ON game_records (chat_id, finished_at);
+    CREATE TABLE IF NOT EXISTS chat_ratings (chat_id INTEGER PRIMARY KEY, ratings TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS seasons (
+        chat_id INTEGER PRIMARY KEY,
+        seasons TEXT NOT NULL,
+        started_at INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS stats (user_id INTEGER PRIMARY KEY, stats TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS ratings (user_id INTEGER PRIMARY KEY, rating TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS badges (user_id INTEGER PRIMARY KEY, badges TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS premium_users (username TEXT PRIMARY KEY);
+    CREATE TABLE IF NOT EXISTS applied_updates (
+        position INTEGER PRIMARY KEY,
+        update_id INTEGER NOT NULL UNIQUE
+    );
+";
+
+const SELECT_GAME: &str = "SELECT state FROM games WHERE chat_id = ?1";
+const UPSERT_GAME: &str =
+    "INSERT OR REPLACE INTO games (chat_id, state, deadline) VALUES (?1, ?2, ?3)";
+const DELETE_GAME: &str = "DELETE FROM games WHERE chat_id = ?1";
+const SELECT_SETTINGS: &str = "SELECT settings FROM settings WHERE chat_id = ?1";
+const UPSERT_SETTINGS: &str = "INSERT OR REPLACE INTO settings (chat_id, settings) VALUES (?1, ?2)";
+const DELETE_SETTINGS: &str = "DELETE FROM settings WHERE chat_id = ?1";
+const SELECT_RECORDS: &str =
+    "SELECT record FROM game_records WHERE chat_id = ?1 ORDER BY finished_at, rowid";
+const INSERT_RECORD: &str =
+    "INSERT INTO game_records (chat_id, finished_at, record) VALUES (?1, ?2, ?3)";
+const MOVE_RECORDS: &str = "UPDATE game_records SET chat_id = ?2 WHERE chat_id = ?1";
+const SELECT_CHAT_RATINGS: &str = "SELECT ratings FROM chat_ratings WHERE chat_id = ?1";
+const UPSERT_CHAT_RATINGS: &str =
+    "INSERT OR REPLACE INTO chat_ratings (chat_id, ratings) VALUES (?1, ?2)";
+const DELETE_CHAT_RATINGS: &str = "DELETE FROM chat_ratings WHERE chat_id = ?1";
+const SELECT_SEASONS: &str = "SELECT seasons FROM seasons WHERE chat_id = ?1";
+const UPSERT_SEASONS: &str =
+    "INSERT OR REPLACE INTO seasons (chat_id, seasons, started_at) VALUES (?1, ?2, ?3)";
+const DELETE_SEASONS: &str = "DELETE FROM seasons WHERE chat_id = ?1";
+const SELECT_STATS: &str = "SELECT stats FROM stats WHERE user_id = ?1";
+const UPSERT_STATS: &str = "INSERT OR REPLACE INTO stats (user_id, stats) VALUES (?1, ?2)";
+const SELECT_RATING: &str = "SELECT rating FROM ratings WHERE user_id = ?1";
+const UPSERT_RATING: &str = "INSERT OR REPLACE INTO ratings (user_id, rating) VALUES (?1, ?2)";
+const SELECT_BADGES: &str = "SELECT badges FROM badges WHERE user_id = ?1";
+const UPSERT_BADGES: &str = "INSERT OR REPLACE INTO badges (user_id, badges) VALUES (?1, ?2)";
+const SELECT_APPLIED_UPDATE: &str = "SELECT 1 FROM applied_updates WHERE update_id = ?1";
+const INSERT_APPLIED_UPDATE: &str = "INSERT OR IGNORE INTO applied_updates (update_id) VALUES (?1)";
+const PRUNE_APPLIED_UPDATES: &str =
+    "DELETE FROM applied_updates WHERE position <= (SELECT max(position) FROM applied_updates) - ?1";
+
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+fn parse<T: DeserializeOwned>(value: String) -> Option<T> {
+    match serde_json::from_str(&value) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            tracing::error!("Can not parse stored value, error: {}", err);
+            None
+        }
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> rusqlite::Result<String> {
+    serde_json::to_string(value)
+        .map_err(|err| rusqlite::Error::ToSqlConversionFailure(Box::new(err)))
+}
+
+fn write_row<T: Serialize>(
+    connection: &Connection,
+    (upsert, delete): (&str, &str),
+    key: i64,
+    value: Option<&T>,
+) -> rusqlite::Result<()> {
+    match value {
+        Some(value) => connection.execute(upsert, params![key, to_json(value)?])?,
+        None => connection.execute(delete, params![key])?,
+    };
+    Ok(())
+}
+
+fn query_chat_ids(
+    connection: &Connection,
+    query: &str,
+    param: i64,
+) -> rusqlite::Result<Vec<telegram_types::ChatId>> {
+    connection.prepare(query).and_then(|mut statement| {
+        statement
+            .query_map(params![param], |row| {
+                row.get(0).map(telegram_types::ChatId::from_i64)
+            })?
+            .collect()
+    })
+}
+
+// Reads inside a transaction. The first failed read is kept so the transaction
+// can be rolled back instead of writing values derived from missing data.
+struct Reader<'a> {
+    connection: &'a Connection,
+    error: RefCell<Option<rusqlite::Error>>,
+}
+
+impl Reader<'_> {
+    fn check<T>(&self, result: rusqlite::Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.error.borrow_mut().get_or_insert(err);
+                None
+            }
+        }
+    }
+
+    fn get<T: DeserializeOwned>(&self, query: &str, key: i64) -> Option<T> {
+        self.check(
+            self.connection
+                .query_row(query, params![key], |row| row.get(0))
+                .optional(),
+        )
+        .flatten()
+        .and_then(parse)
+    }
+}
+
+impl Source for Reader<'_> {
+    fn load_game(&self, chat_id: telegram_types::ChatId) -> Option<game_model::GameState> {
+        self.get(SELECT_GAME, chat_id.as_i64())
+    }
+
+    fn load_settings(&self, chat_id: telegram_types::ChatId) -> Option<settings::ChatSettings> {
+        self.get(SELECT_SETTINGS, chat_id.as_i64())
+    }
+
+    fn load_records(&self, chat_id: telegram_types::ChatId) -> Vec<leaderboard::GameRecord> {
+        let records = self
+            .connection
+            .prepare(SELECT_RECORDS)
+            .and_then(|mut statement| {
+                statement
+                    .query_map(params![chat_id.as_i64()], |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<String>>>()
+            });
+        self.check(records)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(parse)
+            .collect()
+    }
+
+    fn load_chat_ratings(&self, chat_id: telegram_types::ChatId) -> Option<ratings::ChatRatings> {
+        self.get(SELECT_CHAT_RATINGS, chat_id.as_i64())
+    }
+
+    fn load_seasons(&self, chat_id: telegram_types::ChatId) -> Option<seasons::ChatSeasons> {
+        self.get(SELECT_SEASONS, chat_id.as_i64())
+    }
+
+    fn load_stats(&self, user_id: telegram_types::UserId) -> Option<stats::UserStats> {
+        self.get(SELECT_STATS, user_id.as_i64())
+    }
+
+    fn load_rating(&self, user_id: telegram_types::UserId) -> Option<ratings::Rating> {
+        self.get(SELECT_RATING, user_id.as_i64())
+    }
+
+    fn load_badges(&self, user_id: telegram_types::UserId) -> Option<Vec<String>> {
+        self.get(SELECT_BADGES, user_id.as_i64())
+    }
+}
+
+fn apply(connection: &Connection, changes: Changes) -> rusqlite::Result<()> {
+    for (chat_id, game) in changes.games {
+        match game {
+            Some(game) => connection.execute(
+                UPSERT_GAME,
+                params![chat_id.as_i64(), to_json(&game)?, game.deadline() as i64],
+            )?,
+            None => connection.execute(DELETE_GAME, params![chat_id.as_i64()])?,
+        };
+    }
+    for (chat_id, settings) in changes.settings {
+        write_row(
+            connection,
+            (UPSERT_SETTINGS, DELETE_SETTINGS),
+            chat_id.as_i64(),
+            settings.as_ref(),
+        )?;
+    }
+    for (from, to) in changes.moved_records {
+        connection.execute(MOVE_RECORDS, params![from.as_i64(), to.as_i64()])?;
+    }
+    for (chat_id, record) in changes.records {
+        connection.execute(
+            INSERT_RECORD,
+            params![
+                chat_id.as_i64(),
+                record.finished_at as i64,
+                to_json(&record)?
+            ],
+        )?;
+    }
+    for (chat_id, chat_ratings) in changes.chat_ratings {
+        write_row(
+            connection,
+            (UPSERT_CHAT_RATINGS, DELETE_CHAT_RATINGS),
+            chat_id.as_i64(),
+            chat_ratings.as_ref(),
+        )?;
+    }
+    for (chat_id, seasons) in changes.seasons {
+        match seasons {
+            Some(seasons) => connection.execute(
+                UPSERT_SEASONS,
+                params![
+                    chat_id.as_i64(),
+                    to_json(&seasons)?,
+                    seasons
+                        .current
+                        .as_ref()
+                        .map(|season| season.started_at as i64)
+                ],
+            )?,
+            None => connection.execute(DELETE_SEASONS, params![chat_id.as_i64()])?,
+        };
+    }
+    for (user_id, stats) in changes.stats {
+        connection.execute(UPSERT_STATS, params![user_id.as_i64(), to_json(&stats)?])?;
+    }
+    for (user_id, rating) in changes.ratings {
+        connection.execute(UPSERT_RATING, params![user_id.as_i64(), to_json(&rating)?])?;
+    }
+    for (user_id, badges) in changes.badges {
+        connection.execute(UPSERT_BADGES, params![user_id.as_i64(), to_json(&badges)?])?;
+    }
+    Ok(())
+}
+
+impl SqliteStorage {
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<SqliteStorage> {
+        let connection = Connection::open(path)?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(SqliteStorage {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    fn log_error<T>(result: rusqlite::Result<T>) -> Option<T> {
+        result
+            .map_err(|err| tracing::error!("SQLite storage failed, error: {}", err))
+            .ok()
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn transaction(
+        &self,
+        update_id: Option<telegram_types::UpdateId>,
+        update: &mut dyn FnMut(&mut storage::Transaction),
+    ) -> bool {
+        let mut connection = self.lock();
+        Self::log_error(connection.transaction().and_then(|transaction| {
+            let reader = Reader {
+                connection: &transaction,
+                error: RefCell::new(None),
+            };
+            let mut storage_transaction = storage::Transaction::new(&reader);
+            update(&mut storage_transaction);
+            let changes = storage_transaction.into_changes();
+            if let Some(err) = reader.error.into_inner() {
+                return Err(err);
+            }
+            apply(&transaction, changes)?;
+            // The update is marked in the same transaction as its changes, so
+            // after a restart it is skipped exactly when its changes are kept.
+            if let Some(update_id) = update_id {
+                transaction.execute(INSERT_APPLIED_UPDATE, params![update_id.as_i64()])?;
+                transaction.execute(PRUNE_APPLIED_UPDATES, params![MAX_APPLIED_UPDATES as i64])?;
+            }
+            transaction.commit()
+        }))
+        .is_some()
+    }
+
+    fn is_applied(&self, update_id: telegram_types::UpdateId) -> bool {
+        let connection = self.lock();
+        let is_applied = connection
+            .prepare(SELECT_APPLIED_UPDATE)
+            .and_then(|mut statement| statement.exists(params![update_id.as_i64()]));
+        Self::log_error(is_applied).unwrap_or_default()
+    }
+
+    fn get_due_chats(&self, now: u64) -> Vec<telegram_types::ChatId> {
+        Self::log_error(query_chat_ids(
+            &self.lock(),
+            "SELECT chat_id FROM games WHERE deadline <= ?1",
+            now as i64,
+        ))
+        .unwrap_or_default()
+    }
+
+    fn get_season_chats(&self, started_before: u64) -> Vec<telegram_types::ChatId> {
+        Self::log_error(query_chat_ids(
+            &self.lock(),
+            "SELECT chat_id FROM seasons WHERE started_at < ?1",
+            started_before as i64,
+        ))
+        .unwrap_or_default()
+    }
+
+    fn get_premium_usernames(&self) -> HashSet<String> {
+        let connection = self.lock();
+        let usernames = connection
+            .prepare("SELECT username FROM premium_users")
+            .and_then(|mut statement| {
+                statement
+                    .query_map([], |row| row.get(0))?
+                    .collect::<rusqlite::Result<HashSet<String>>>()
+            });
+        Self::log_error(usernames).unwrap_or_default()
+    }
+
+    fn set_premium_usernames(&self, usernames: &[String]) {
+        let mut connection = self.lock();
+        Self::log_error(connection.transaction().and_then(|transaction| {
+            transaction.execute("DELETE FROM premium_users", [])?;
+            for username in usernames {
+                transaction.execute(
+                    "INSERT OR IGNORE INTO premium_users (username) VALUES (?1)",
+                    params![username],
+                )?;
+            }
+            transaction.commit()
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transactions_are_read_back_after_migration() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        let (group, supergroup) = (
+            telegram_types::ChatId::from_i64(-1),
+            telegram_types::ChatId::from_i64(-100),
+        );
+        let winner: telegram_types::UserId = serde_json::from_str("7").unwrap();
+        storage.transaction(None, &mut |transaction| {
+            transaction.settings_mut(group).language = settings::Language::Persian;
+            transaction.game_or_new(group);
+            transaction.add_record(
+                group,
+                leaderboard::GameRecord {
+                    finished_at: 10,
+                    players: vec![],
+                    winner,
+                },
+            );
+            transaction.badges_mut(winner).push("lucky".to_string());
+        });
+        storage.transaction(None, &mut |transaction| {
+            transaction.migrate_chat(group, supergroup)
+        });
+        storage.transaction(None, &mut |transaction| {
+            assert!(transaction.game_mut(group).is_none());
+            assert!(transaction.game_mut(supergroup).is_some());
+            assert!(transaction.settings(supergroup).language == settings::Language::Persian);
+            assert_eq!(transaction.records(supergroup).len(), 1);
+            assert!(transaction.records(group).is_empty());
+            assert_eq!(transaction.badges(winner), vec!["lucky".to_string()]);
+        });
+        assert!(storage.get_due_chats(0).is_empty());
+        assert!(storage.get_due_chats(game_model::unix_now() + 24 * 60 * 60) == vec![supergroup]);
+    }
+
+    #[test]
+    fn applied_updates_are_remembered() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        let update_id = |id: i64| serde_json::from_value(serde_json::json!(id)).unwrap();
+        let player: telegram_types::UserId = serde_json::from_str("7").unwrap();
+        assert!(storage.transaction(Some(update_id(5)), &mut |transaction| {
+            transaction.stats_mut(player).games_played += 1;
+        }));
+        assert!(storage.is_applied(update_id(5)));
+        assert!(!storage.is_applied(update_id(4)));
+        assert!(!storage.is_applied(update_id(6)));
+    }
+
+    #[test]
+    fn premium_users_are_replaced() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        storage.set_premium_usernames(&["alice".to_string(), "bob".to_string()]);
+        storage.set_premium_usernames(&["bob".to_string()]);
+        assert_eq!(
+            storage.get_premium_usernames(),
+            HashSet::from(["bob".to_string()])
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+
+use super::game_model;
+use super::message_action;
+use super::ratings;
+use super::storage;
+use super::telegram_types;
+use crate::achievements::get_achievement;
+use crate::prompt_messages::{
+    badges_title, no_badges, no_stats, profile_summary, profile_title, user_rating, user_stats,
+};
+use crate::settings::Language;
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+pub struct UserStats {
+    pub games_played: u32,
+    pub wins: u32,
+    pub total_rolls: u32,
+    pub ones_rolled: u32,
+    pub busted_points: u32,
+    pub largest_turn: u16,
+    pub holds: u32,
+    pub held_points: u32,
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    pub faces: [u32; 6],
+}
+
+impl UserStats {
+    pub fn average_hold(&self) -> f64 {
+        if self.holds == 0 {
+            0.0
+        } else {
+            self.held_points as f64 / self.holds as f64
+        }
+    }
+
+    fn add_game(&mut self, player: &game_model::FinishedPlayer, is_winner: bool) {
+        self.games_played += 1;
+        self.total_rolls += player.tally.rolls;
+        self.ones_rolled += player.tally.ones;
+        self.busted_points += player.tally.busted_points;
+        self.holds += player.tally.holds;
+        self.held_points += player.tally.held_points;
+        self.largest_turn = self.largest_turn.max(player.tally.largest_turn);
+        for (face, count) in self.faces.iter_mut().zip(player.tally.faces) {
+            *face += count;
+        }
+        if is_winner {
+            self.wins += 1;
+            self.current_streak += 1;
+            self.longest_streak = self.longest_streak.max(self.current_streak);
+        } else {
+            self.current_streak = 0;
+        }
+    }
+}
+
+pub fn record(transaction: &mut storage::Transaction, finished_game: &game_model::FinishedGame) {
+    for player in &finished_game.players {
+        transaction
+            .stats_mut(player.user_id)
+            .add_game(player, player.user_id == finished_game.winner);
+    }
+}
+
+fn get_target_user(message: &telegram_types::Message) -> Option<&telegram_types::User> {
+    message
+        .reply_to_message
+        .as_ref()
+        .and_then(|replied| replied.from.as_ref())
+        .or(message.from.as_ref())
+}
+
+pub fn handle_command(
+    transaction: &storage::Transaction,
+    message: &telegram_types::Message,
+    language: Language,
+) -> Option<message_action::MessageAction> {
+    let user = get_target_user(message)?;
+    let text = match transaction.stats(user.id) {
+        Some(stats) => {
+            let chat_rating = match message.chat.chat_type {
+                telegram_types::ChatType::Private => None,
+                _ => Some(
+                    ratings::get_chat_rating(
+                        transaction.chat_ratings(message.chat.id).as_ref(),
+                        user.id,
+                    )
+                    .value,
+                ),
+            };
+            format!(
+                "{}\n{}",
+                user_stats(language, &user.first_name, &stats),
+                user_rating(language, chat_rating, transaction.rating(user.id).value)
+            )
+        }
+        None => no_stats(language, &user.first_name),
+    };
+    Some(message_action::MessageAction::Send(
+        message_action::MessageInfo {
+            text,
+            reply_to_message_id: Some(message.message_id),
+            reply_markup: None,
+            hint: None,
+            is_premium: false,
+            parse_mode: None,
+        },
+    ))
+}
+
+pub fn handle_profile_command(
+    transaction: &storage::Transaction,
+    message: &telegram_types::Message,
+    language: Language,
+) -> Option<message_action::MessageAction> {
+    let user = get_target_user(message)?;
+    let stats = transaction.stats(user.id).unwrap_or_default();
+    let badges = transaction.badges(user.id);
+    let badges_text = match badges.is_empty() {
+        false => badges.iter().filter_map(|id| get_achievement(id)).fold(
+            badges_title(language).to_string(),
+            |res, achievement| {
+                format!(
+                    "{}\n- {} {}: {}",
+                    res, achievement.emoji, achievement.name, achievement.description
+                )
+            },
+        ),
+        true => no_badges(language).to_string(),
+    };
+    Some(message_action::MessageAction::Send(
+        message_action::MessageInfo {
+            text: format!(
+                "{}\n{}\n\n{}",
+                profile_title(language, &user.first_name),
+                profile_summary(
+                    language,
+                    stats.games_played,
+                    stats.wins,
+                    transaction.rating(user.id).value
+                ),
+                badges_text
+            ),
+            reply_to_message_id: Some(message.message_id),
+            reply_markup: None,
+            hint: None,
+            is_premium: false,
+            parse_mode: None,
+        },
+    ))
+}
@@ -0,0 +1,550 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Mutex, MutexGuard},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::game_model;
+use super::journal::Journal;
+use super::leaderboard;
+use super::ratings;
+use super::seasons;
+use super::settings;
+use super::stats;
+use super::telegram_types;
+
+// How many of the latest applied update ids are remembered, see `is_applied`.
+pub const MAX_APPLIED_UPDATES: usize = 10_000;
+
+pub trait Storage: Send + Sync {
+    // Runs `update` against the stored data and persists everything it changed
+    // at once, so an update is either stored completely or not at all. Returns
+    // false when nothing was stored, in which case the caller must not act on
+    // what `update` decided.
+    fn transaction(
+        &self,
+        update_id: Option<telegram_types::UpdateId>,
+        update: &mut dyn FnMut(&mut Transaction),
+    ) -> bool;
+
+    // Whether a transaction for the update was stored before a restart.
+    fn is_applied(&self, update_id: telegram_types::UpdateId) -> bool;
+
+    // Chats whose game deadline has passed, see `GameState::deadline`.
+    fn get_due_chats(&self, now: u64) -> Vec<telegram_types::ChatId>;
+
+    // Chats whose current season started before `started_before`.
+    fn get_season_chats(&self, started_before: u64) -> Vec<telegram_types::ChatId>;
+
+    fn get_premium_usernames(&self) -> HashSet<String>;
+
+    // Replaces the stored premium users, so names removed from the file lose
+    // premium on the next start.
+    fn set_premium_usernames(&self, usernames: &[String]);
+}
+
+// Stored data as seen by a transaction before any of its own changes.
+pub trait Source {
+    fn load_game(&self, chat_id: telegram_types::ChatId) -> Option<game_model::GameState>;
+
+    fn load_settings(&self, chat_id: telegram_types::ChatId) -> Option<settings::ChatSettings>;
+
+    fn load_records(&self, chat_id: telegram_types::ChatId) -> Vec<leaderboard::GameRecord>;
+
+    fn load_chat_ratings(&self, chat_id: telegram_types::ChatId) -> Option<ratings::ChatRatings>;
+
+    fn load_seasons(&self, chat_id: telegram_types::ChatId) -> Option<seasons::ChatSeasons>;
+
+    fn load_stats(&self, user_id: telegram_types::UserId) -> Option<stats::UserStats>;
+
+    fn load_rating(&self, user_id: telegram_types::UserId) -> Option<ratings::Rating>;
+
+    fn load_badges(&self, user_id: telegram_types::UserId) -> Option<Vec<String>>;
+}
+
+// Everything a transaction wrote. Chat values set to `None` are removed.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Changes {
+    pub games: HashMap<telegram_types::ChatId, Option<game_model::GameState>>,
+    pub settings: HashMap<telegram_types::ChatId, Option<settings::ChatSettings>>,
+    pub records: Vec<(telegram_types::ChatId, leaderboard::GameRecord)>,
+    pub moved_records: Vec<(telegram_types::ChatId, telegram_types::ChatId)>,
+    pub chat_ratings: HashMap<telegram_types::ChatId, Option<ratings::ChatRatings>>,
+    pub seasons: HashMap<telegram_types::ChatId, Option<seasons::ChatSeasons>>,
+    pub stats: HashMap<telegram_types::UserId, stats::UserStats>,
+    pub ratings: HashMap<telegram_types::UserId, ratings::Rating>,
+    pub badges: HashMap<telegram_types::UserId, Vec<String>>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty()
+            && self.settings.is_empty()
+            && self.records.is_empty()
+            && self.moved_records.is_empty()
+            && self.chat_ratings.is_empty()
+            && self.seasons.is_empty()
+            && self.stats.is_empty()
+            && self.ratings.is_empty()
+            && self.badges.is_empty()
+    }
+}
+
+// Values are loaded from the source on first access and kept in the changes
+// once they are borrowed mutably, so later reads see the transaction's writes.
+pub struct Transaction<'a> {
+    source: &'a dyn Source,
+    changes: Changes,
+}
+
+impl<'a> Transaction<'a> {
+    pub fn new(source: &'a dyn Source) -> Transaction<'a> {
+        Transaction {
+            source,
+            changes: Changes::default(),
+        }
+    }
+
+    pub fn into_changes(self) -> Changes {
+        self.changes
+    }
+
+    // Reads the game without storing it back, so looking at a chat neither
+    // creates a game nor counts as activity.
+    pub fn game(&self, chat_id: telegram_types::ChatId) -> Option<game_model::GameState> {
+        match self.changes.games.get(&chat_id) {
+            Some(game) => game.clone(),
+            None => self.source.load_game(chat_id),
+        }
+    }
+
+    pub fn game_mut(
+        &mut self,
+        chat_id: telegram_types::ChatId,
+    ) -> Option<&mut game_model::GameState> {
+        let source = self.source;
+        self.changes
+            .games
+            .entry(chat_id)
+            .or_insert_with(|| source.load_game(chat_id))
+            .as_mut()
+    }
+
+    pub fn game_or_new(&mut self, chat_id: telegram_types::ChatId) -> &mut game_model::GameState {
+        let settings = self.settings(chat_id);
+        let source = self.source;
+        self.changes
+            .games
+            .entry(chat_id)
+            .or_insert_with(|| source.load_game(chat_id))
+            .get_or_insert_with(|| game_model::GameState::new(settings))
+    }
+
+    pub fn remove_game(&mut self, chat_id: telegram_types::ChatId) {
+        self.changes.games.insert(chat_id, None);
+    }
+
+    pub fn settings(&self, chat_id: telegram_types::ChatId) -> settings::ChatSettings {
+        match self.changes.settings.get(&chat_id) {
+            Some(settings) => *settings,
+            None => self.source.load_settings(chat_id),
+        }
+        .unwrap_or_default()
+    }
+
+    pub fn settings_mut(&mut self, chat_id: telegram_types::ChatId) -> &mut settings::ChatSettings {
+        let source = self.source;
+        self.changes
+            .settings
+            .entry(chat_id)
+            .or_insert_with(|| source.load_settings(chat_id))
+            .get_or_insert_with(Default::default)
+    }
+
+    pub fn records(&self, chat_id: telegram_types::ChatId) -> Vec<leaderboard::GameRecord> {
+        let mut records = self.source.load_records(chat_id);
+        records.extend(
+            self.changes
+                .records
+                .iter()
+                .filter(|(record_chat_id, _)| *record_chat_id == chat_id)
+                .map(|(_, record)| record.clone()),
+        );
+        records
+    }
+
+    pub fn add_record(&mut self, chat_id: telegram_types::ChatId, record: leaderboard::GameRecord) {
+        self.changes.records.push((chat_id, record));
+    }
+
+    pub fn chat_ratings(&self, chat_id: telegram_types::ChatId) -> Option<ratings::ChatRatings> {
+        match self.changes.chat_ratings.get(&chat_id) {
+            Some(chat_ratings) => chat_ratings.clone(),
+            None => self.source.load_chat_ratings(chat_id),
+        }
+    }
+
+    pub fn chat_ratings_mut(
+        &mut self,
+        chat_id: telegram_types::ChatId,
+    ) -> &mut ratings::ChatRatings {
+        let source = self.source;
+        self.changes
+            .chat_ratings
+            .entry(chat_id)
+            .or_insert_with(|| source.load_chat_ratings(chat_id))
+            .get_or_insert_with(Default::default)
+    }
+
+    pub fn seasons(&self, chat_id: telegram_types::ChatId) -> Option<seasons::ChatSeasons> {
+        match self.changes.seasons.get(&chat_id) {
+            Some(seasons) => seasons.clone(),
+            None => self.source.load_seasons(chat_id),
+        }
+    }
+
+    pub fn seasons_mut(&mut self, chat_id: telegram_types::ChatId) -> &mut seasons::ChatSeasons {
+        let source = self.source;
+        self.changes
+            .seasons
+            .entry(chat_id)
+            .or_insert_with(|| source.load_seasons(chat_id))
+            .get_or_insert_with(Default::default)
+    }
+
+    pub fn stats(&self, user_id: telegram_types::UserId) -> Option<stats::UserStats> {
+        self.changes
+            .stats
+            .get(&user_id)
+            .copied()
+            .or_else(|| self.source.load_stats(user_id))
+    }
+
+    pub fn stats_mut(&mut self, user_id: telegram_types::UserId) -> &mut stats::UserStats {
+        let source = self.source;
+        self.changes
+            .stats
+            .entry(user_id)
+            .or_insert_with(|| source.load_stats(user_id).unwrap_or_default())
+    }
+
+    pub fn rating(&self, user_id: telegram_types::UserId) -> ratings::Rating {
+        self.changes
+            .ratings
+            .get(&user_id)
+            .copied()
+            .or_else(|| self.source.load_rating(user_id))
+            .unwrap_or_default()
+    }
+
+    pub fn set_rating(&mut self, user_id: telegram_types::UserId, rating: ratings::Rating) {
+        self.changes.ratings.insert(user_id, rating);
+    }
+
+    pub fn badges(&self, user_id: telegram_types::UserId) -> Vec<String> {
+        match self.changes.badges.get(&user_id) {
+            Some(badges) => badges.clone(),
+            None => self.source.load_badges(user_id).unwrap_or_default(),
+        }
+    }
+
+    pub fn badges_mut(&mut self, user_id: telegram_types::UserId) -> &mut Vec<String> {
+        let source = self.source;
+        self.changes
+            .badges
+            .entry(user_id)
+            .or_insert_with(|| source.load_badges(user_id).unwrap_or_default())
+    }
+
+    // Telegram reports an upgrade to a supergroup both in the old group and in
+    // the new supergroup, so whichever arrives first moves the chat's data.
+    // Settings the new chat already has are kept, while games, records,
+    // ratings and seasons of both chats are merged.
+    pub fn migrate_chat(&mut self, from: telegram_types::ChatId, to: telegram_types::ChatId) {
+        let source = self.source;
+        move_chat_value(
+            &mut self.changes.games,
+            (from, to),
+            |chat_id| source.load_game(chat_id),
+            game_model::GameState::merge,
+        );
+        move_chat_value(
+            &mut self.changes.settings,
+            (from, to),
+            |chat_id| source.load_settings(chat_id),
+            |_, _| (),
+        );
+        move_chat_value(
+            &mut self.changes.chat_ratings,
+            (from, to),
+            |chat_id| source.load_chat_ratings(chat_id),
+            ratings::merge,
+        );
+        move_chat_value(
+            &mut self.changes.seasons,
+            (from, to),
+            |chat_id| source.load_seasons(chat_id),
+            seasons::ChatSeasons::merge,
+        );
+        self.changes.moved_records.push((from, to));
+    }
+}
+
+fn move_chat_value<T>(
+    changes: &mut HashMap<telegram_types::ChatId, Option<T>>,
+    (from, to): (telegram_types::ChatId, telegram_types::ChatId),
+    load: impl Fn(telegram_types::ChatId) -> Option<T>,
+    merge: impl FnOnce(&mut T, T),
+) {
+    let value = changes.remove(&from).unwrap_or_else(|| load(from));
+    changes.insert(from, None);
+    let Some(value) = value else {
+        return;
+    };
+    match changes.entry(to).or_insert_with(|| load(to)) {
+        Some(existing) => merge(existing, value),
+        existing => *existing = Some(value),
+    }
+}
+
+fn apply_chat_changes<T>(
+    values: &mut HashMap<telegram_types::ChatId, T>,
+    changes: HashMap<telegram_types::ChatId, Option<T>>,
+) {
+    for (chat_id, value) in changes {
+        match value {
+            Some(value) => values.insert(chat_id, value),
+            None => values.remove(&chat_id),
+        };
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Data {
+    #[serde(default)]
+    games: HashMap<telegram_types::ChatId, game_model::GameState>,
+    #[serde(default)]
+    settings: HashMap<telegram_types::ChatId, settings::ChatSettings>,
+    #[serde(default)]
+    records: HashMap<telegram_types::ChatId, Vec<leaderboard::GameRecord>>,
+    #[serde(default)]
+    chat_ratings: HashMap<telegram_types::ChatId, ratings::ChatRatings>,
+    #[serde(default)]
+    seasons: HashMap<telegram_types::ChatId, seasons::ChatSeasons>,
+    #[serde(default)]
+    stats: HashMap<telegram_types::UserId, stats::UserStats>,
+    #[serde(default)]
+    ratings: HashMap<telegram_types::UserId, ratings::Rating>,
+    #[serde(default)]
+    badges: HashMap<telegram_types::UserId, Vec<String>>,
+    #[serde(default)]
+    premium_usernames: HashSet<String>,
+}
+
+impl Data {
+    pub fn apply(&mut self, changes: Changes) {
+        apply_chat_changes(&mut self.games, changes.games);
+        apply_chat_changes(&mut self.settings, changes.settings);
+        apply_chat_changes(&mut self.chat_ratings, changes.chat_ratings);
+        apply_chat_changes(&mut self.seasons, changes.seasons);
+        for (from, to) in changes.moved_records {
+            if let Some(mut records) = self.records.remove(&from) {
+                let chat_records = self.records.entry(to).or_default();
+                chat_records.append(&mut records);
+                chat_records.sort_by_key(|record| record.finished_at);
+            }
+        }
+        for (chat_id, record) in changes.records {
+            self.records.entry(chat_id).or_default().push(record);
+        }
+        self.stats.extend(changes.stats);
+        self.ratings.extend(changes.ratings);
+        self.badges.extend(changes.badges);
+    }
+}
+
+impl Source for Data {
+    fn load_game(&self, chat_id: telegram_types::ChatId) -> Option<game_model::GameState> {
+        self.games.get(&chat_id).cloned()
+    }
+
+    fn load_settings(&self, chat_id: telegram_types::ChatId) -> Option<settings::ChatSettings> {
+        self.settings.get(&chat_id).copied()
+    }
+
+    fn load_records(&self, chat_id: telegram_types::ChatId) -> Vec<leaderboard::GameRecord> {
+        self.records.get(&chat_id).cloned().unwrap_or_default()
+    }
+
+    fn load_chat_ratings(&self, chat_id: telegram_types::ChatId) -> Option<ratings::ChatRatings> {
+        self.chat_ratings.get(&chat_id).cloned()
+    }
+
+    fn load_seasons(&self, chat_id: telegram_types::ChatId) -> Option<seasons::ChatSeasons> {
+        self.seasons.get(&chat_id).cloned()
+    }
+
+    fn load_stats(&self, user_id: telegram_types::UserId) -> Option<stats::UserStats> {
+        self.stats.get(&user_id).copied()
+    }
+
+    fn load_rating(&self, user_id: telegram_types::UserId) -> Option<ratings::Rating> {
+        self.ratings.get(&user_id).copied()
+    }
+
+    fn load_badges(&self, user_id: telegram_types::UserId) -> Option<Vec<String>> {
+        self.badges.get(&user_id).cloned()
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    data: Data,
+    journal: Option<Journal>,
+}
+
+// All data sits behind one lock, which is held for the whole transaction, so
+// transactions never see each other's partial changes.
+#[derive(Default)]
+pub struct InMemoryStorage {
+    inner: Mutex<Inner>,
+}
+
+impl InMemoryStorage {
+    pub fn new(data: Data, journal: Option<Journal>) -> InMemoryStorage {
+        InMemoryStorage {
+            inner: Mutex::new(Inner { data, journal }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    pub fn compact(&self) -> std::io::Result<()> {
+        let mut inner = self.lock();
+        let Inner { data, journal } = &mut *inner;
+        match journal {
+            Some(journal) => journal.compact(data),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Storage for InMemoryStorage {
+    fn transaction(
+        &self,
+        update_id: Option<telegram_types::UpdateId>,
+        update: &mut dyn FnMut(&mut Transaction),
+    ) -> bool {
+        let mut inner = self.lock();
+        let Inner { data, journal } = &mut *inner;
+        let mut transaction = Transaction::new(&*data);
+        update(&mut transaction);
+        let changes = transaction.into_changes();
+        // The entry is written before the changes are applied and the lock is
+        // released. Changes that can not be journaled are dropped, so replies
+        // are only sent for changes a restart keeps.
+        if let Some(journal) = journal {
+            if let Err(err) = journal.append(update_id, &changes) {
+                tracing::error!("Can not write journal entry, error: {}", err);
+                return false;
+            }
+        }
+        data.apply(changes);
+        true
+    }
+
+    fn is_applied(&self, update_id: telegram_types::UpdateId) -> bool {
+        self.lock()
+            .journal
+            .as_ref()
+            .is_some_and(|journal| journal.is_applied(update_id))
+    }
+
+    fn get_due_chats(&self, now: u64) -> Vec<telegram_types::ChatId> {
+        self.lock()
+            .data
+            .games
+            .iter()
+            .filter(|(_, game)| game.deadline() <= now)
+            .map(|(chat_id, _)| *chat_id)
+            .collect()
+    }
+
+    fn get_season_chats(&self, started_before: u64) -> Vec<telegram_types::ChatId> {
+        self.lock()
+            .data
+            .seasons
+            .iter()
+            .filter(|(_, seasons)| {
+                seasons
+                    .current
+                    .as_ref()
+                    .is_some_and(|season| season.started_at < started_before)
+            })
+            .map(|(chat_id, _)| *chat_id)
+            .collect()
+    }
+
+    fn get_premium_usernames(&self) -> HashSet<String> {
+        self.lock().data.premium_usernames.clone()
+    }
+
+    fn set_premium_usernames(&self, usernames: &[String]) {
+        self.lock().data.premium_usernames = usernames.iter().cloned().collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migration_merges_both_chats() {
+        let storage = InMemoryStorage::default();
+        let (group, supergroup) = (
+            telegram_types::ChatId::from_i64(-1),
+            telegram_types::ChatId::from_i64(-100),
+        );
+        let veteran: telegram_types::UserId = serde_json::from_str("1").unwrap();
+        let newcomer: telegram_types::UserId = serde_json::from_str("2").unwrap();
+        storage.transaction(None, &mut |transaction| {
+            transaction.settings_mut(group).target_score = 50;
+            transaction.settings_mut(supergroup).target_score = 200;
+            transaction.chat_ratings_mut(group).insert(
+                veteran,
+                ratings::Rating {
+                    value: 1600.0,
+                    games: 10,
+                },
+            );
+            let chat_ratings = transaction.chat_ratings_mut(supergroup);
+            chat_ratings.insert(
+                veteran,
+                ratings::Rating {
+                    value: 1510.0,
+                    games: 1,
+                },
+            );
+            chat_ratings.insert(
+                newcomer,
+                ratings::Rating {
+                    value: 1490.0,
+                    games: 1,
+                },
+            );
+        });
+        storage.transaction(None, &mut |transaction| {
+            transaction.migrate_chat(group, supergroup)
+        });
+        storage.transaction(None, &mut |transaction| {
+            assert_eq!(transaction.settings(supergroup).target_score, 200);
+            let chat_ratings = transaction.chat_ratings(supergroup).unwrap();
+            assert_eq!(chat_ratings[&veteran].games, 10);
+            assert_eq!(chat_ratings[&newcomer].games, 1);
+            assert!(transaction.chat_ratings(group).is_none());
+        });
+    }
+}
@@ -0,0 +1,222 @@
+use std::sync::OnceLock;
+
+use super::game_model;
+use super::message_action;
+use crate::prompt_messages::{decision_entry, decisions_title, risk_profile};
+use crate::settings::{Language, TARGET_SCORES};
+
+const CONVERGENCE: f32 = 1e-6;
+// Differences smaller than this are rounding noise, so both choices count as optimal.
+const TOLERANCE: f64 = 1e-4;
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum Decision {
+    Roll,
+    Hold,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum RiskProfile {
+    Optimal,
+    Balanced,
+    HoldsTooEarly,
+    TooGreedy,
+}
+
+pub struct Evaluation {
+    pub is_optimal: bool,
+    pub win_chance_lost: f64,
+}
+
+// Win probabilities of the optimal two-player Pig policy (Neller & Presser) for
+// the player to move with score `i`, opponent score `j` and turn total `k`.
+// With more than two players the strongest opponent stands in for the rest.
+struct Policy {
+    target: usize,
+    offsets: Vec<usize>,
+    values: Vec<f32>,
+}
+
+impl Policy {
+    fn new(target: usize) -> Policy {
+        let mut offsets = Vec::with_capacity(target);
+        let mut size = 0;
+        for i in 0..target {
+            offsets.push(size);
+            size += target * (target - i);
+        }
+        let mut policy = Policy {
+            target,
+            offsets,
+            values: vec![0.0; size],
+        };
+        policy.solve();
+        policy
+    }
+
+    fn index(&self, i: usize, j: usize, k: usize) -> usize {
+        self.offsets[i] + j * (self.target - i) + k
+    }
+
+    fn get(&self, i: usize, j: usize, k: usize) -> f32 {
+        if i + k >= self.target {
+            1.0
+        } else if j >= self.target {
+            0.0
+        } else {
+            self.values[self.index(i, j, k)]
+        }
+    }
+
+    fn hold_value(&self, i: usize, j: usize, k: usize) -> f32 {
+        1.0 - self.get(j, i + k, 0)
+    }
+
+    fn roll_value(&self, i: usize, j: usize, k: usize) -> f32 {
+        (1.0 - self.get(j, i, 0) + (2..=6).map(|roll| self.get(i, j, k + roll)).sum::<f32>()) / 6.0
+    }
+
+    fn solve_chain(&mut self, i: usize, j: usize) {
+        for k in (0..self.target - i).rev() {
+            let roll = self.roll_value(i, j, k);
+            // Holding on an empty turn total just passes the dice, which is never
+            // better than rolling, so the first roll of a turn is always forced.
+            let value = if k == 0 {
+                roll
+            } else {
+                roll.max(self.hold_value(i, j, k))
+            };
+            let index = self.index(i, j, k);
+            self.values[index] = value;
+        }
+    }
+
+    // States only lead to higher score sums, except for busting which swaps
+    // the two players, so each pair is solved by iterating its two chains
+    // after all pairs with a higher sum are known.
+    fn solve(&mut self) {
+        for sum in (0..=2 * (self.target - 1)).rev() {
+            for i in sum.saturating_sub(self.target - 1)..=sum.min(self.target - 1) {
+                let j = sum - i;
+                if i > j {
+                    continue;
+                }
+                loop {
+                    let previous = (self.get(i, j, 0), self.get(j, i, 0));
+                    self.solve_chain(i, j);
+                    self.solve_chain(j, i);
+                    if (self.get(i, j, 0) - previous.0).abs() < CONVERGENCE
+                        && (self.get(j, i, 0) - previous.1).abs() < CONVERGENCE
+                    {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+static POLICIES: [OnceLock<Policy>; TARGET_SCORES.len()] =
+    [const { OnceLock::new() }; TARGET_SCORES.len()];
+
+fn get_policy_cell(target: u16) -> Option<&'static OnceLock<Policy>> {
+    TARGET_SCORES
+        .iter()
+        .position(|target_score| *target_score == target)
+        .map(|index| &POLICIES[index])
+}
+
+// Solving a policy takes a noticeable moment for large targets, so they are
+// only computed here, on a blocking thread at startup. Each target has its own
+// cell, so a solved policy can be read while the next one is being solved.
+pub fn warm_up() {
+    for target in TARGET_SCORES {
+        if let Some(cell) = get_policy_cell(target) {
+            cell.get_or_init(|| Policy::new(target as usize));
+        }
+    }
+}
+
+// Decisions made before the policy of their target is solved are not scored.
+pub fn evaluate(
+    target: u16,
+    score: u16,
+    opponent_score: u16,
+    turn_score: u16,
+    decision: Decision,
+) -> Option<Evaluation> {
+    let policy = get_policy_cell(target)?.get()?;
+    let (i, j, k) = (score as usize, opponent_score as usize, turn_score as usize);
+    let roll = policy.roll_value(i, j, k) as f64;
+    let hold = policy.hold_value(i, j, k) as f64;
+    let chosen = match decision {
+        Decision::Roll => roll,
+        Decision::Hold => hold,
+    };
+    let win_chance_lost = (roll.max(hold) - chosen).max(0.0);
+    Some(Evaluation {
+        is_optimal: win_chance_lost < TOLERANCE,
+        win_chance_lost,
+    })
+}
+
+fn get_risk_profile(tally: &game_model::PlayerTally) -> RiskProfile {
+    if tally.early_holds == 0 && tally.greedy_rolls == 0 {
+        RiskProfile::Optimal
+    } else if tally.early_holds > tally.greedy_rolls {
+        RiskProfile::HoldsTooEarly
+    } else if tally.greedy_rolls > tally.early_holds {
+        RiskProfile::TooGreedy
+    } else {
+        RiskProfile::Balanced
+    }
+}
+
+pub fn report(
+    finished_game: &game_model::FinishedGame,
+    language: Language,
+) -> Option<message_action::MessageAction> {
+    let lines = finished_game
+        .players
+        .iter()
+        .filter(|player| player.tally.decisions > 0)
+        .fold("".to_string(), |res, player| {
+            format!(
+                "{}\n{}\n{}",
+                res,
+                decision_entry(
+                    language,
+                    &player.name,
+                    player.tally.optimal_decisions as f64 * 100.0 / player.tally.decisions as f64,
+                    player.tally.win_chance_lost * 100.0
+                ),
+                risk_profile(language, get_risk_profile(&player.tally))
+            )
+        });
+    if lines.is_empty() {
+        return None;
+    }
+    Some(message_action::MessageAction::Send(
+        message_action::MessageInfo {
+            text: format!("{}{}", decisions_title(language), lines),
+            reply_to_message_id: None,
+            reply_markup: None,
+            hint: None,
+            is_premium: false,
+            parse_mode: None,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_player_has_the_edge() {
+        let policy = Policy::new(100);
+        assert!((policy.get(0, 0, 0) - 0.5306).abs() < 1e-4);
+        // Any roll but a one wins, and after a one the opponent is in the same spot.
+        assert!((policy.get(99, 99, 0) - 6.0 / 7.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,426 @@
+use std::time::Duration;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::message_action::{EditMessageInfo, MessageInfo};
+use super::rate_limiter::{RateLimiter, Scope};
+use super::telegram_types;
+
+const DEFAULT_API_URL: &str = "https://api.telegram.org";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_ATTEMPTS: u32 = 5;
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+const ALLOWED_UPDATES: [&str; 2] = ["message", "callback_query"];
+
+pub struct TelegramClient {
+    client: reqwest::Client,
+    api_url: String,
+    bot_token: String,
+    rate_limiter: RateLimiter,
+}
+
+#[derive(Serialize)]
+struct ChatMessage<'a, Info: Serialize> {
+    chat_id: telegram_types::ChatId,
+    #[serde(flatten)]
+    info: &'a Info,
+}
+
+#[derive(Serialize)]
+struct SendDice {
+    chat_id: telegram_types::ChatId,
+    emoji: &'static str,
+}
+
+#[derive(Serialize)]
+struct AnswerCallbackQuery<'a> {
+    callback_query_id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct GetChatMember {
+    chat_id: telegram_types::ChatId,
+    user_id: telegram_types::UserId,
+}
+
+#[derive(Serialize)]
+struct GetUpdates {
+    offset: Option<telegram_types::UpdateId>,
+    timeout: u64,
+    allowed_updates: [&'static str; 2],
+}
+
+#[derive(Serialize)]
+struct SetMyCommands<'a> {
+    commands: &'a [telegram_types::BotCommand],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language_code: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct SetWebhook<'a> {
+    url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret_token: Option<&'a str>,
+    allowed_updates: [&'static str; 2],
+}
+
+#[derive(Serialize)]
+struct Empty {}
+
+impl TelegramClient {
+    pub fn new(
+        api_url: String,
+        bot_token: String,
+        timeout: Duration,
+        rate_limiter: RateLimiter,
+    ) -> TelegramClient {
+        TelegramClient {
+            client: reqwest::Client::builder()
+                .timeout(timeout)
+                .connect_timeout(CONNECT_TIMEOUT)
+                .build()
+                .expect("HTTP client can not be built"),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            bot_token,
+            rate_limiter,
+        }
+    }
+
+    pub fn from_env() -> TelegramClient {
+        TelegramClient::new(
+            std::env::var("TELEGRAM_API_URL").unwrap_or(DEFAULT_API_URL.to_string()),
+            std::env::var("BOT_TOKEN").expect("BOT_TOKEN environment variable is not set"),
+            std::env::var("TELEGRAM_TIMEOUT")
+                .ok()
+                .and_then(|seconds| seconds.parse().ok())
+                .map_or(DEFAULT_TIMEOUT, Duration::from_secs),
+            RateLimiter::new(),
+        )
+    }
+
+    async fn post<T: Serialize>(
+        &self,
+        method: &str,
+        body: &T,
+        timeout: Option<Duration>,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let request = self
+            .client
+            .post(format!("{}/bot{}/{}", self.api_url, self.bot_token, method))
+            .json(body);
+        match timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
+        }
+        .send()
+        .await
+    }
+
+    async fn parse<R: DeserializeOwned>(
+        result: Result<reqwest::Response, reqwest::Error>,
+    ) -> Option<R> {
+        let response = match result {
+            Ok(res) if res.status().is_success() => res,
+            Ok(res) => {
+                match res.text().await {
+                    Ok(body) => {
+                        tracing::error!("Telegram API call was not success, {}", body);
+                    }
+                    Err(_) => {
+                        tracing::error!("Telegram API call was not success, can not extract response text neither");
+                    }
+                }
+                return None;
+            }
+            Err(err) => {
+                tracing::error!("Can not send a request to Telegram, error: {}", err);
+                return None;
+            }
+        };
+        match response.json::<telegram_types::ApiResult<R>>().await {
+            Ok(result) => Some(result.result),
+            Err(err) => {
+                tracing::error!("Can not parse Telegram response, error: {}", err);
+                None
+            }
+        }
+    }
+
+    // Requests wait for the rate limiter when a scope is given. Throttled
+    // requests are retried after the delay Telegram asks for, server and network
+    // errors with an exponential backoff. Callers await every retry, so messages
+    // of a chat keep their order.
+    async fn call<T: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        body: &T,
+        scope: Option<Scope>,
+    ) -> Option<R> {
+        let mut backoff = MIN_RETRY_DELAY;
+        for attempt in 1..=MAX_ATTEMPTS {
+            if let Some(scope) = scope {
+                self.rate_limiter.acquire(scope).await;
+            }
+            let delay = match self.post(method, body, None).await {
+                Ok(res) if res.status() == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                    let retry_after = res
+                        .json::<telegram_types::ErrorResponse>()
+                        .await
+                        .ok()
+                        .and_then(|error| error.parameters)
+                        .and_then(|parameters| parameters.retry_after)
+                        .map_or(backoff, Duration::from_secs);
+                    tracing::warn!(
+                        "Telegram throttled {}, retrying in {} s",
+                        method,
+                        retry_after.as_secs()
+                    );
+                    retry_after
+                }
+                Ok(res) if res.status().is_server_error() => {
+                    tracing::warn!(
+                        "Telegram failed {} with {}, retrying in {} s",
+                        method,
+                        res.status(),
+                        backoff.as_secs()
+                    );
+                    backoff
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        "Can not send {} to Telegram, retrying in {} s, error: {}",
+                        method,
+                        backoff.as_secs(),
+                        err
+                    );
+                    backoff
+                }
+                result => return Self::parse(result).await,
+            };
+            if attempt < MAX_ATTEMPTS {
+                tokio::time::sleep(delay).await;
+                backoff = (backoff * 2).min(MAX_RETRY_DELAY);
+            }
+        }
+        tracing::error!("Giving up on {} after {} attempts", method, MAX_ATTEMPTS);
+        None
+    }
+
+    // Only new messages count towards the per-chat limit, edits share the
+    // global one and rely on retry_after if Telegram still throttles them.
+    pub async fn send_message(
+        &self,
+        chat_id: telegram_types::ChatId,
+        info: &MessageInfo,
+    ) -> Option<telegram_types::Message> {
+        self.call(
+            "sendMessage",
+            &ChatMessage { chat_id, info },
+            Some(Scope::Chat(chat_id)),
+        )
+        .await
+    }
+
+    pub async fn edit_message_text(
+        &self,
+        chat_id: telegram_types::ChatId,
+        info: &EditMessageInfo,
+    ) -> Option<telegram_types::Message> {
+        self.call(
+            "editMessageText",
+            &ChatMessage { chat_id, info },
+            Some(Scope::Global),
+        )
+        .await
+    }
+
+    // Players roll their own dice, so the game does not call this yet.
+    #[allow(dead_code)]
+    pub async fn send_dice(
+        &self,
+        chat_id: telegram_types::ChatId,
+    ) -> Option<telegram_types::Message> {
+        self.call(
+            "sendDice",
+            &SendDice {
+                chat_id,
+                emoji: "🎲",
+            },
+            Some(Scope::Chat(chat_id)),
+        )
+        .await
+    }
+
+    pub async fn answer_callback_query(&self, callback_query_id: &str, text: Option<&str>) -> bool {
+        self.call::<_, bool>(
+            "answerCallbackQuery",
+            &AnswerCallbackQuery {
+                callback_query_id,
+                text,
+            },
+            None,
+        )
+        .await
+        .is_some()
+    }
+
+    pub async fn get_chat_member(
+        &self,
+        chat_id: telegram_types::ChatId,
+        user_id: telegram_types::UserId,
+    ) -> Option<telegram_types::ChatMember> {
+        self.call("getChatMember", &GetChatMember { chat_id, user_id }, None)
+            .await
+    }
+
+    pub async fn is_chat_admin(
+        &self,
+        chat_id: telegram_types::ChatId,
+        user_id: telegram_types::UserId,
+    ) -> bool {
+        self.get_chat_member(chat_id, user_id)
+            .await
+            .is_some_and(|member| {
+                matches!(
+                    member.status,
+                    telegram_types::ChatMemberStatus::Creator
+                        | telegram_types::ChatMemberStatus::Administrator
+                )
+            })
+    }
+
+    // Long polling is retried by the caller, so it is sent only once and may
+    // take longer than the default timeout.
+    pub async fn get_updates(
+        &self,
+        offset: Option<telegram_types::UpdateId>,
+        timeout: u64,
+    ) -> Option<Vec<telegram_types::Update>> {
+        let body = GetUpdates {
+            offset,
+            timeout,
+            allowed_updates: ALLOWED_UPDATES,
+        };
+        Self::parse(
+            self.post(
+                "getUpdates",
+                &body,
+                Some(Duration::from_secs(timeout) + DEFAULT_TIMEOUT),
+            )
+            .await,
+        )
+        .await
+    }
+
+    pub async fn get_me(&self) -> Option<telegram_types::User> {
+        self.call("getMe", &Empty {}, None).await
+    }
+
+    pub async fn set_my_commands(
+        &self,
+        commands: &[telegram_types::BotCommand],
+        language_code: Option<&str>,
+    ) -> bool {
+        self.call::<_, bool>(
+            "setMyCommands",
+            &SetMyCommands {
+                commands,
+                language_code,
+            },
+            None,
+        )
+        .await
+        .is_some()
+    }
+
+    pub async fn set_webhook(&self, url: &str, secret_token: Option<&str>) -> bool {
+        self.call::<_, bool>(
+            "setWebhook",
+            &SetWebhook {
+                url,
+                secret_token,
+                allowed_updates: ALLOWED_UPDATES,
+            },
+            None,
+        )
+        .await
+        .is_some()
+    }
+
+    // getUpdates is refused by Telegram while a webhook is registered.
+    pub async fn delete_webhook(&self) -> bool {
+        self.call::<_, bool>("deleteWebhook", &Empty {}, None)
+            .await
+            .is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use serde_json::json;
+    use tokio::time::Instant;
+
+    use super::*;
+    use crate::mock_telegram::MockTelegram;
+
+    fn client(mock: &MockTelegram) -> TelegramClient {
+        TelegramClient::new(
+            mock.url.clone(),
+            "TEST".to_string(),
+            Duration::from_secs(5),
+            RateLimiter::unlimited(),
+        )
+    }
+
+    #[tokio::test]
+    async fn throttled_calls_are_retried_after_retry_after() {
+        let mock = MockTelegram::start().await;
+        mock.fail_next(
+            StatusCode::TOO_MANY_REQUESTS,
+            json!({
+                "ok": false,
+                "error_code": 429,
+                "description": "Too Many Requests: retry after 2",
+                "parameters": {"retry_after": 2},
+            }),
+        );
+        let started = Instant::now();
+        assert!(client(&mock).get_me().await.is_some());
+        assert!(started.elapsed() >= Duration::from_secs(2));
+        let methods: Vec<String> = mock
+            .take_calls()
+            .into_iter()
+            .map(|call| call.method)
+            .collect();
+        assert_eq!(methods, vec!["getMe", "getMe"]);
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_retried_with_backoff() {
+        let mock = MockTelegram::start().await;
+        mock.fail_next(StatusCode::BAD_GATEWAY, json!({"ok": false}));
+        let started = Instant::now();
+        assert!(client(&mock).get_me().await.is_some());
+        assert!(started.elapsed() >= MIN_RETRY_DELAY);
+        assert_eq!(mock.take_calls().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn dice_are_sent_to_the_chat() {
+        let mock = MockTelegram::start().await;
+        let chat_id = telegram_types::ChatId::from_i64(-1);
+        let message = client(&mock).send_dice(chat_id).await.unwrap();
+        assert!(message
+            .dice
+            .is_some_and(|dice| (1..=6).contains(&dice.value)));
+        let calls = mock.take_calls();
+        assert_eq!(calls[0].method, "sendDice");
+        assert_eq!(calls[0].body, json!({"chat_id": -1, "emoji": "🎲"}));
+    }
+}
@@ -1,26 +1,57 @@
 use serde::{Deserialize, Serialize};
 
+use super::command;
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
 #[serde(transparent)]
 pub struct MessageId(i64);
 
-#[derive(Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
 #[serde(transparent)]
 pub struct UserId(i64);
 
+impl UserId {
+    pub fn as_i64(self) -> i64 {
+        self.0
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
 #[serde(transparent)]
 pub struct ChatId(i64);
 
-#[derive(Deserialize, PartialEq, Eq)]
+impl ChatId {
+    pub fn from_i64(id: i64) -> ChatId {
+        ChatId(id)
+    }
+
+    pub fn as_i64(self) -> i64 {
+        self.0
+    }
+
+    pub fn is_group(&self) -> bool {
+        self.0 < 0
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 #[serde(transparent)]
 pub struct UpdateId(i64);
 
+impl UpdateId {
+    pub fn as_i64(self) -> i64 {
+        self.0
+    }
+
+    pub fn next(self) -> UpdateId {
+        UpdateId(self.0 + 1)
+    }
+}
+
 #[derive(Deserialize)]
 pub struct User {
     pub id: UserId,
     pub first_name: String,
-    pub last_name: Option<String>,
     pub username: Option<String>,
 }
 
@@ -41,7 +72,6 @@ pub struct Chat {
     pub id: ChatId,
     #[serde(rename = "type")]
     pub chat_type: ChatType,
-    pub username: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -50,6 +80,33 @@ pub struct MessageEntity {
     pub length: usize,
     #[serde(rename = "type")]
     pub entity_type: String,
+    pub user: Option<User>,
+}
+
+// Converts an offset counted in UTF-16 code units, as Telegram does, to a byte
+// offset. Offsets past the end or in the middle of a character give None.
+fn utf16_to_byte_offset(text: &str, offset: usize) -> Option<usize> {
+    let mut units = 0;
+    for (index, char) in text.char_indices() {
+        if units >= offset {
+            return (units == offset).then_some(index);
+        }
+        units += char.len_utf16();
+    }
+    (units == offset).then_some(text.len())
+}
+
+impl MessageEntity {
+    fn byte_range(&self, text: &str) -> Option<(usize, usize)> {
+        let start = utf16_to_byte_offset(text, self.offset)?;
+        let end = utf16_to_byte_offset(text, self.offset.checked_add(self.length)?)?;
+        Some((start, end))
+    }
+}
+
+pub enum Mention<'a> {
+    Username(&'a str),
+    User(&'a User),
 }
 
 #[derive(Deserialize)]
@@ -96,15 +153,51 @@ pub struct Message {
     pub dice: Option<Dice>,
     pub entities: Option<Vec<MessageEntity>>,
     pub forward_date: Option<i64>,
+    pub reply_to_message: Option<Box<Message>>,
+    pub migrate_to_chat_id: Option<ChatId>,
+    pub migrate_from_chat_id: Option<ChatId>,
 }
 
 impl Message {
-    pub fn get_commands(&self) -> Vec<String> {
+    pub fn get_migration(&self) -> Option<(ChatId, ChatId)> {
+        match (self.migrate_to_chat_id, self.migrate_from_chat_id) {
+            (Some(to), _) => Some((self.chat.id, to)),
+            (None, Some(from)) => Some((from, self.chat.id)),
+            (None, None) => None,
+        }
+    }
+
+    pub fn get_commands(&self) -> Vec<command::ParsedCommand> {
+        let (Some(entities), Some(text)) = (&self.entities, &self.text) else {
+            return Vec::new();
+        };
+        let ranges: Vec<(usize, usize)> = entities
+            .iter()
+            .filter(|entity| entity.entity_type == "bot_command")
+            .filter_map(|entity| entity.byte_range(text))
+            .collect();
+        ranges
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &(start, end))| {
+                let args_end = ranges.get(index + 1).map_or(text.len(), |next| next.0);
+                command::parse(text.get(start..end)?, text.get(end..args_end)?)
+            })
+            .collect()
+    }
+
+    pub fn get_mentions(&self) -> Vec<Mention<'_>> {
         match (&self.entities, &self.text) {
             (Some(entity), Some(text)) => entity
                 .iter()
-                .filter(|entity| entity.entity_type == "bot_command")
-                .map(|entity| text[entity.offset..entity.offset + entity.length].to_string())
+                .filter_map(|entity| match (entity.entity_type.as_str(), &entity.user) {
+                    ("mention", _) => entity
+                        .byte_range(text)
+                        .and_then(|(start, end)| text.get(start + 1..end))
+                        .map(Mention::Username),
+                    ("text_mention", Some(user)) => Some(Mention::User(user)),
+                    _ => None,
+                })
                 .collect(),
             _ => Vec::new(),
         }
@@ -126,8 +219,131 @@ pub struct Update {
     pub callback_query: Option<CallbackQuery>,
 }
 
+impl Update {
+    pub fn get_chat_id(&self) -> Option<ChatId> {
+        self.message
+            .as_ref()
+            .or(self
+                .callback_query
+                .as_ref()
+                .and_then(|callback_query| callback_query.message.as_ref()))
+            .map(|message| message.chat.id)
+    }
+}
+
+#[derive(Deserialize)]
+pub enum ChatMemberStatus {
+    #[serde(rename = "creator")]
+    Creator,
+    #[serde(rename = "administrator")]
+    Administrator,
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+pub struct ChatMember {
+    pub status: ChatMemberStatus,
+}
+
 #[derive(Deserialize)]
-pub struct ResultMessage {
-    pub ok: bool,
-    pub result: Message,
+pub struct ApiResult<T> {
+    pub result: T,
+}
+
+#[derive(Deserialize)]
+pub struct ResponseParameters {
+    pub retry_after: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct ErrorResponse {
+    pub parameters: Option<ResponseParameters>,
+}
+
+#[derive(Serialize)]
+pub struct BotCommand {
+    pub command: String,
+    pub description: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    fn message(text: &str, entities: &[(&str, usize, usize)]) -> Message {
+        let entities: Vec<serde_json::Value> = entities
+            .iter()
+            .map(|(entity_type, offset, length)| {
+                serde_json::json!({"type": entity_type, "offset": offset, "length": length})
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "message_id": 1,
+            "chat": {"id": -1, "type": "group"},
+            "text": text,
+            "entities": entities,
+        }))
+        .unwrap()
+    }
+
+    fn commands(message: &Message) -> Vec<(Command, Vec<String>)> {
+        message
+            .get_commands()
+            .into_iter()
+            .map(|parsed| (parsed.command, parsed.args))
+            .collect()
+    }
+
+    #[test]
+    fn command_after_emoji() {
+        // 🎲 is two UTF-16 code units but four bytes.
+        let message = message("🎲🎲 /top season 2", &[("bot_command", 5, 4)]);
+        assert_eq!(
+            commands(&message),
+            vec![(Command::Top, vec!["season".into(), "2".into()])]
+        );
+    }
+
+    #[test]
+    fn command_after_persian_text() {
+        let message = message("سلام /join", &[("bot_command", 5, 5)]);
+        assert_eq!(commands(&message), vec![(Command::Join, vec![])]);
+    }
+
+    #[test]
+    fn commands_after_cyrillic_text() {
+        let message = message(
+            "Привет /hold и /result",
+            &[("bot_command", 7, 5), ("bot_command", 15, 7)],
+        );
+        assert_eq!(
+            commands(&message),
+            vec![(Command::Hold, vec!["и".into()]), (Command::Result, vec![])]
+        );
+    }
+
+    #[test]
+    fn mention_after_emoji() {
+        let message = message("👋 @bob", &[("mention", 3, 4)]);
+        let mentions = message.get_mentions();
+        assert!(matches!(mentions.as_slice(), [Mention::Username("bob")]));
+    }
+
+    #[test]
+    fn malformed_entities_are_skipped() {
+        let message = message(
+            "🎲 /play",
+            &[
+                ("bot_command", 1, 5),
+                ("bot_command", 3, 100),
+                ("bot_command", usize::MAX, 2),
+                ("mention", 1, 3),
+                ("bot_command", 3, 5),
+            ],
+        );
+        assert_eq!(commands(&message), vec![(Command::Play, vec![])]);
+        assert!(message.get_mentions().is_empty());
+    }
 }
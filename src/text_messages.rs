@@ -1,2 +1,17 @@
 pub const DICE_EMOJI: &str = "🎲";
 pub const KING_EMOJI: &str = "👑";
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn bold(html: &str) -> String {
+    format!("<b>{}</b>", html)
+}
+
+pub fn monospace(text: &str) -> String {
+    format!("<code>{}</code>", escape_html(text))
+}
@@ -0,0 +1,41 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Mutex,
+};
+
+use super::telegram_types;
+
+const WINDOW_SIZE: usize = 1000;
+
+#[derive(Default)]
+struct Window {
+    ids: HashSet<telegram_types::UpdateId>,
+    order: VecDeque<telegram_types::UpdateId>,
+}
+
+// Remembers the most recently seen update ids. Ids are inserted before the
+// update is processed, so a retry arriving while the first delivery is still
+// running is skipped as well.
+#[derive(Default)]
+pub struct UpdateWindow {
+    window: Mutex<Window>,
+}
+
+impl UpdateWindow {
+    pub fn insert(&self, update_id: telegram_types::UpdateId) -> bool {
+        let mut window = self
+            .window
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        if !window.ids.insert(update_id) {
+            return false;
+        }
+        window.order.push_back(update_id);
+        if window.order.len() > WINDOW_SIZE {
+            if let Some(oldest) = window.order.pop_front() {
+                window.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+}